@@ -0,0 +1,48 @@
+// tag_safe_attrs
+//
+// Companion attributes for the tag_safe lint
+//
+// Author: John Hodge (thePowersGang/Mutabah)
+//
+//! Provides `#[tag_safe::req(..)]`, `#[tag_safe::is(..)]` and `#[tag_safe::not(..)]` for crates
+//! that can't use `#![register_tool(tag_safe)]` (e.g. on stable).
+//!
+//! Import this crate under the name `tag_safe`
+//! ```toml
+//! [dependencies]
+//! tag_safe = { package = "tag_safe_attrs", version = "0.1" }
+//! ```
+//!
+//! Outside of `tag-safe-driver` the attributes expand to nothing. When the driver is used (which
+//! sets `--cfg tag_safe`) they expand to the equivalent bare attribute for the checker to read.
+extern crate proc_macro;
+
+use proc_macro::TokenStream;
+
+fn forward(name: &str, attr: TokenStream, item: TokenStream) -> TokenStream {
+    let mut rv: TokenStream = format!("#[cfg_attr(tag_safe, {}({}))]", name, attr)
+        .parse()
+        .expect("tag_safe: Malformed attribute arguments");
+    rv.extend(item);
+    rv
+}
+
+/// Require that the function only calls functions that are safe for the listed tags
+#[proc_macro_attribute]
+pub fn req(attr: TokenStream, item: TokenStream) -> TokenStream {
+    forward("req_safe", attr, item)
+}
+
+/// Mark the function as safe for the listed tags
+#[proc_macro_attribute]
+pub fn is(attr: TokenStream, item: TokenStream) -> TokenStream {
+    forward("is_safe", attr, item)
+}
+
+/// Mark the function as not safe for the listed tags
+#[proc_macro_attribute]
+pub fn not(attr: TokenStream, item: TokenStream) -> TokenStream {
+    forward("not_safe", attr, item)
+}
+
+// vim: ts=4 expandtab sw=4
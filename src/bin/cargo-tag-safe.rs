@@ -0,0 +1,83 @@
+// cargo-tag-safe
+//
+// Cargo subcommand that runs the tag_safe analysis over a workspace
+//
+// Author: John Hodge (thePowersGang/Mutabah)
+//
+//! `cargo tag-safe [--export <dir> [--export-ids]] [cargo check options]`
+//!
+//! Runs `cargo check` with `tag-safe-driver` as the `RUSTC_WORKSPACE_WRAPPER`, so every crate in
+//! the workspace (but not its dependencies) is checked.
+use std::env;
+use std::path::PathBuf;
+use std::process::{exit, Command};
+
+const USAGE: &str = "Checks a workspace using the tag_safe lints
+
+Usage:
+    cargo tag-safe [options] [--] [<opts>...]
+
+Options:
+    --export <dir>      Write the tags of each crate's public functions to <dir>/<crate>.tags
+    --export-ids        Name functions by their DefPathHash in exported lists, instead of by path
+
+All other options are passed through to `cargo check`. Crates that cargo considers up to date
+aren't checked again, so use `cargo clean -p <crate>` to re-export a crate's tags.
+";
+
+/// The driver lives next to this binary (both are installed by `cargo install tag_safe`)
+fn driver_path() -> PathBuf {
+    let mut path = env::current_exe().expect("current executable path invalid");
+    path.set_file_name(if cfg!(windows) { "tag-safe-driver.exe" } else { "tag-safe-driver" });
+    path
+}
+
+/// Remove `name <value>` (or `name=<value>`) from the arguments, returning the value
+fn take_option(args: &mut Vec<String>, name: &str) -> Option<String> {
+    let prefix = format!("{}=", name);
+    let pos = args.iter().take_while(|a| *a != "--").position(|a| a == name || a.starts_with(&prefix))?;
+    let arg = args.remove(pos);
+    if arg == name {
+        if pos < args.len() {
+            Some(args.remove(pos))
+        }
+        else {
+            eprintln!("Missing value for {}", name);
+            exit(1);
+        }
+    }
+    else {
+        Some(arg[prefix.len()..].to_owned())
+    }
+}
+
+fn main() {
+    // Invoked as `cargo-tag-safe tag-safe [args]` by cargo
+    let mut args: Vec<String> = env::args().skip(2).collect();
+
+    if args.iter().any(|a| a == "--help" || a == "-h") {
+        println!("{}", USAGE);
+        return;
+    }
+
+    let mut cmd = Command::new(env::var("CARGO").unwrap_or_else(|_| "cargo".to_owned()));
+    if let Some(dir) = take_option(&mut args, "--export") {
+        // rustc is run from each package's directory, so the path must be absolute
+        let dir = env::current_dir().expect("current directory invalid").join(dir);
+        cmd.env("TAG_SAFE_EXPORT", dir);
+    }
+    if let Some(pos) = args.iter().take_while(|a| *a != "--").position(|a| a == "--export-ids") {
+        args.remove(pos);
+        cmd.env("TAG_SAFE_EXPORT_IDS", "1");
+    }
+
+    let status = cmd
+        .arg("check")
+        .args(&args)
+        .env("RUSTC_WORKSPACE_WRAPPER", driver_path())
+        .status()
+        .expect("could not run cargo");
+    exit(status.code().unwrap_or(-1));
+}
+
+// vim: ts=4 expandtab sw=4
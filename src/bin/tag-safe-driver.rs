@@ -0,0 +1,95 @@
+// tag-safe-driver
+//
+// A wrapper around rustc that registers the tag_safe lints
+//
+// Author: John Hodge (thePowersGang/Mutabah)
+//
+//! Drop-in replacement for `rustc` that runs the `tag_safe` analysis.
+//!
+//! Can either be invoked directly (`tag-safe-driver foo.rs`) or used as a cargo
+//! `RUSTC_WORKSPACE_WRAPPER`, in which case the first argument is the path to rustc.
+#![feature(rustc_private)]
+
+extern crate rustc_driver;
+extern crate rustc_interface;
+extern crate tag_safe;
+
+use rustc_driver::Compilation;
+use rustc_interface::interface;
+use std::path::Path;
+use std::process::{exit, Command};
+
+struct TagSafeCallbacks;
+
+impl rustc_driver::Callbacks for TagSafeCallbacks
+{
+    fn config(&mut self, config: &mut interface::Config) {
+        let previous = config.register_lints.take();
+        config.register_lints = Some(Box::new(move |sess, lint_store| {
+            if let Some(ref previous) = previous {
+                previous(sess, lint_store);
+            }
+            tag_safe::register_lints(sess, lint_store);
+        }));
+    }
+
+    fn after_expansion(&mut self, compiler: &interface::Compiler) -> Compilation {
+        let expansion = compiler.expansion().expect("tag_safe: Expansion failed");
+        let (ref krate, ..) = *expansion.peek();
+        tag_safe::load_tag_lists(compiler.session(), krate);
+        Compilation::Continue
+    }
+}
+
+/// Locate the sysroot, as this binary isn't part of the toolchain
+fn get_sysroot() -> Option<String> {
+    if let Ok(v) = std::env::var("SYSROOT") {
+        return Some(v);
+    }
+    let out = Command::new("rustc").arg("--print").arg("sysroot").output().ok()?;
+    Some( String::from_utf8(out.stdout).ok()?.trim().to_owned() )
+}
+
+fn main() {
+    rustc_driver::init_rustc_env_logger();
+    rustc_driver::install_ice_hook();
+
+    let mut args: Vec<String> = std::env::args().collect();
+
+    // When used as `RUSTC_WORKSPACE_WRAPPER`, cargo passes the real rustc as the first argument
+    if args.len() > 1 && Path::new(&args[1]).file_stem() == Some("rustc".as_ref()) {
+        args.remove(1);
+    }
+
+    // Probes from cargo (e.g. `rustc -vV`) don't need the lints
+    let is_probe = args.iter().any(|a| a == "-vV" || a == "-V" || a == "--version" || a.starts_with("--print"));
+
+    if !args.iter().any(|a| a == "--sysroot" || a.starts_with("--sysroot=")) {
+        if let Some(sysroot) = get_sysroot() {
+            args.push("--sysroot".to_owned());
+            args.push(sysroot);
+        }
+    }
+
+    if !is_probe {
+        // Allow the bare attributes to be used without `#![feature(custom_attribute)]`
+        args.push("-Zcrate-attr=feature(register_attr)".to_owned());
+        args.push(format!("-Zcrate-attr=register_attr({})", tag_safe::ATTRIBUTES.join(",")));
+        // Lets `tag_safe_attrs` emit the bare attributes
+        args.push("--cfg".to_owned());
+        args.push("tag_safe".to_owned());
+    }
+
+    let rv = rustc_driver::report_ices_to_stderr_if_any(move || {
+            if is_probe {
+                rustc_driver::run_compiler(&args, &mut rustc_driver::DefaultCallbacks, None, None)
+            }
+            else {
+                rustc_driver::run_compiler(&args, &mut TagSafeCallbacks, None, None)
+            }
+        })
+        .and_then(|result| result);
+    exit(if rv.is_err() { 1 } else { 0 });
+}
+
+// vim: ts=4 expandtab sw=4
@@ -3,19 +3,38 @@ use syntax::ast;
 use syntax::ast::{MetaItemKind,NestedMetaItem};
 use rustc::hir::def_id::DefId;
 use rustc::hir::def;
-use syntax::source_map::{Span, Symbol};
+use syntax::source_map::{Span, Symbol, DesugaringKind, ExpnKind, DUMMY_SP};
 use rustc::lint::{self, LintContext, LintPass, LateLintPass, LintArray};
-use rustc::ty::{TyCtxt};
+use rustc::mir;
+use rustc::session::config::CrateType;
+use rustc::ty::{self, Ty, TyCtxt};
+use rustc::ty::adjustment::Adjust;
+use rustc::ty::subst::{InternalSubsts, Subst, SubstsRef};
 use rustc::hir::{self, ExprKind, ItemKind};
+use rustc_errors::DiagnosticBuilder;
+use std::collections::{HashMap, HashSet};
+use database::{Reason, ExternPolicy, Severity};
 
 declare_lint!(NOT_TAGGED_SAFE, Warn, "Warn about use of non-tagged methods within tagged function");
+declare_lint!(TAG_SAFE_ASSUMED_EXTERN, Warn, "Warn when an extern function without any tag information is assumed to be safe");
+declare_lint!(TAG_SAFE_STALE_ENTRY, Warn, "Warn about tag list entries that don't match any function of their crate");
 
 pub struct Pass
 {
-    visit_stack: Vec<hir::HirId>,
-	pub sym_issafe: Symbol,
-	pub sym_notsafe: Symbol,
-	pub sym_reqsafe: Symbol,
+    visit_stack: Vec<VisitFrame>,
+    // Nodes that have been visited but whose strongly connected component isn't complete (Tarjan's stack)
+    scc_stack: Vec<(hir::HirId, ::database::Tag)>,
+    // Visit index for each node on `scc_stack`
+    scc_index: HashMap<(hir::HirId, ::database::Tag), usize>,
+    next_index: usize,
+    // Extern functions that have been reported as assumed safe (for tags with `extern_policy = "warn"`)
+    assumed_externs: HashSet<(DefId, ::database::Tag)>,
+    // Newly assumed extern functions, reported at the call site in the function being linted
+    pending_assumptions: Vec<(DefId, ::database::Tag)>,
+	sym_issafe: AttrName,
+	sym_notsafe: AttrName,
+	sym_reqsafe: AttrName,
+	sym_config: AttrName,
 }
 impl Pass
 {
@@ -23,23 +42,123 @@ impl Pass
 	{
 		Pass {
 			visit_stack: Vec::new(),
-			sym_issafe: Symbol::intern("is_safe"),
-			sym_notsafe: Symbol::intern("not_safe"),
-			sym_reqsafe: Symbol::intern("req_safe"),
+			scc_stack: Vec::new(),
+			scc_index: HashMap::new(),
+			next_index: 0,
+			assumed_externs: HashSet::new(),
+			pending_assumptions: Vec::new(),
+			sym_issafe: AttrName::new("is_safe", "is"),
+			sym_notsafe: AttrName::new("not_safe", "not"),
+			sym_reqsafe: AttrName::new("req_safe", "req"),
+			sym_config: AttrName::new("tag_safe_config", "config"),
 			}
 	}
 }
 
+/// A function currently being checked by `method_is_safe`
+struct VisitFrame
+{
+    index: usize,
+    // Lowest index reachable from this node that is still on the SCC stack
+    lowlink: usize,
+}
+
+/// Name of a tagging attribute, either the bare form (`#[req_safe(..)]`) or the tool form (`#[tag_safe::req(..)]`)
+#[derive(Copy,Clone)]
+pub struct AttrName
+{
+    bare: Symbol,
+    tool_ns: Symbol,
+    tool: Symbol,
+}
+impl AttrName
+{
+    pub fn new(bare: &str, tool: &str) -> Self
+    {
+        AttrName {
+            bare: Symbol::intern(bare),
+            tool_ns: Symbol::intern("tag_safe"),
+            tool: Symbol::intern(tool),
+            }
+    }
+
+    pub fn matches(&self, path: &ast::Path) -> bool
+    {
+        match path.segments.len()
+        {
+        1 => path.segments[0].ident.name == self.bare,
+        2 => path.segments[0].ident.name == self.tool_ns && path.segments[1].ident.name == self.tool,
+        _ => false,
+        }
+    }
+}
+
+/// An operation that isn't safe, found while checking a function body
+pub struct Violation<'a>
+{
+    pub span: Span,
+    /// The function being called, if known
+    pub callee: Option<DefId>,
+    /// Description of the operation when there's no known callee (e.g. a call through a function pointer)
+    pub what: &'a str,
+    /// Note describing a hidden call (e.g. one generated by desugaring)
+    pub note: Option<&'a str>,
+}
+impl<'a> Violation<'a>
+{
+    pub fn reason(&self) -> Reason
+    {
+        match self.callee
+        {
+        Some(callee) => Reason::Call(self.span, callee),
+        None => Reason::Operation(self.span, self.what.to_string()),
+        }
+    }
+}
+
 impl LintPass for Pass {
 	fn name(&self) -> &'static str {
 		"tag_safe"
 	}
     fn get_lints(&self) -> LintArray {
-        lint_array!(NOT_TAGGED_SAFE)
+        lint_array!(NOT_TAGGED_SAFE, TAG_SAFE_ASSUMED_EXTERN, TAG_SAFE_STALE_ENTRY)
     }
 }
 
 impl<'a,'b> LateLintPass<'a,'b> for Pass {
+    fn check_crate(&mut self, cx: &lint::LateContext, krate: &hir::Crate) {
+        ::config::load_crate_config(cx.sess(), &krate.attrs, self.sym_config);
+    }
+
+    fn check_crate_post(&mut self, cx: &lint::LateContext, krate: &hir::Crate) {
+        // Report list entries that no longer name anything (e.g. after a typo, or the function was removed)
+        for (span, msg) in ::database::CACHE.read().unwrap().stale_entries(&cx.tcx)
+        {
+            match span
+            {
+            Some(span) => cx.span_lint(TAG_SAFE_STALE_ENTRY, span, &msg),
+            None => cx.lint(TAG_SAFE_STALE_ENTRY, &msg),
+            }
+        }
+
+        // Write out the tags of this crate's public functions, for use by dependent crates (only libraries can have any)
+        let is_library = cx.sess().crate_types.borrow().iter().any(|t| match *t
+            {
+            CrateType::Rlib | CrateType::Dylib => true,
+            _ => false,
+            });
+        let export_dir = ::std::env::var(::export::EXPORT_VAR).ok();
+        if is_library || export_dir.is_some() {
+            ::export::add_dependency_tags(self, cx, krate);
+        }
+        if is_library {
+            ::export::write_results(self, cx, krate);
+        }
+        if let Some(dir) = export_dir {
+            ::export::export_tags(self, cx, krate, &dir);
+        }
+    }
+
     fn check_fn(&mut self, cx: &lint::LateContext, _kind: hir::intravisit::FnKind, _decl: &hir::FnDecl, body: &hir::Body, _: Span, id: hir::HirId) {
         let attrs = cx.tcx.hir().attrs(id);
 
@@ -51,10 +170,11 @@ impl<'a,'b> LateLintPass<'a,'b> for Pass {
                 let tag = lh.get_tag_or_add(&tag_name.as_str());
                 lh.mark(id, tag,  true);
             }
-            for tag_name in get_tags(attrs, self.sym_notsafe.clone())
+            for (tag_name, span) in get_tag_spans(attrs, self.sym_notsafe.clone())
             {
                 let tag = lh.get_tag_or_add(&tag_name.as_str());
                 lh.mark(id, tag,  false);
+                lh.set_reason(id, tag, Reason::Attribute(span));
             }
         }
         
@@ -69,22 +189,102 @@ impl<'a,'b> LateLintPass<'a,'b> for Pass {
                 tag
                 };
 
+            if ::database::CACHE.read().unwrap().tag_options(ty_tag).severity == Severity::Allow {
+                continue ;
+            }
+
             // Search body for calls to non safe methods
-            let mut v = Visitor {
-                    pass: self, cx: cx, tag: ty_tag,
-                    cb: |span| {
-                            cx.span_lint(NOT_TAGGED_SAFE, *span,
-                                &format!("Calling {0}-unsafe method from a #[req_safe({0})] method", tag_name)[..]
-                                );
-                        },
-                    };
+            let report = |v: &Violation| {
+                    let mut db = match tag_diagnostic(cx, ty_tag, v.span,
+                        &format!("Calling {0}-unsafe method from a #[req_safe({0})] method", tag_name)
+                        )
+                        {
+                        Some(v) => v,
+                        None => return,
+                        };
+                    match v.callee
+                    {
+                    Some(callee) => { db.span_label(v.span, format!("calls `{}`", cx.tcx.def_path_str(callee))); },
+                    None => { db.span_label(v.span, v.what); },
+                    }
+                    if let Some(note) = v.note {
+                        db.note(note);
+                    }
+                    if let Some(callee) = v.callee {
+                        explain_chain(&mut db, cx.tcx, callee, ty_tag, &tag_name.as_str());
+                    }
+                    db.emit();
+                };
             debug!("Method {:?} is marked safe '{}'", id, tag_name);
-            hir::intravisit::walk_body(&mut v, body);
+            if ::database::CACHE.read().unwrap().backend() == ::database::Backend::Mir {
+                ::mir::check_fn(self, cx, cx.tcx.hir().local_def_id(id), ty_tag, report);
+            }
+            else {
+                let mut v = Visitor::new(self, cx, body.id(), ty_tag, report).as_root();
+                hir::intravisit::Visitor::visit_body(&mut v, body);
+            }
         }
 
         // TODO: For all known safeties (that aren't already set) populate.
         // - Requires knowing all safeties (which we don't, ... yet)
     }
+
+    fn check_item(&mut self, cx: &lint::LateContext, item: &hir::Item) {
+        match item.kind
+        {
+        // Trait implementations must satisfy the tags required by the trait
+        ItemKind::Impl(.., Some(ref trait_ref), _, ref impl_items) => {
+            self.check_trait_impl(cx, item, trait_ref.trait_def_id(), impl_items);
+            },
+        // Function pointers stored in tagged statics
+        ItemKind::Static(_, _, body) => {
+            let tables = cx.tcx.body_tables(body);
+            self.check_fn_store(cx, tables, &cx.tcx.hir().body(body).value, &item.attrs, "static");
+            },
+        _ => {},
+        }
+    }
+
+    fn check_expr(&mut self, cx: &lint::LateContext, ex: &hir::Expr) {
+        // Function pointers stored in tagged fields and parameters
+        match ex.kind
+        {
+        ExprKind::Struct(ref qp, ref fields, _) => {
+            if let ty::Adt(adt, _) = cx.tables.expr_ty(ex).kind {
+                let variant = adt.variant_of_res(cx.tables.qpath_res(qp, ex.hir_id));
+                for f in fields.iter()
+                {
+                    let field_def = &variant.fields[cx.tcx.field_index(f.hir_id, cx.tables)];
+                    self.check_fn_store(cx, cx.tables, &f.expr, &cx.tcx.get_attrs(field_def.did), "field");
+                }
+            }
+            },
+        ExprKind::Assign(ref dst, ref value) => {
+            if let Some(attrs) = slot_attrs(cx.tcx, cx.tables, dst) {
+                self.check_fn_store(cx, cx.tables, value, &attrs, "slot");
+            }
+            },
+        ExprKind::Call(ref fcn, ref args) => {
+            if let ty::FnDef(did, _) = cx.tables.expr_ty(fcn).kind {
+                for (i, a) in args.iter().enumerate() {
+                    if let Some(attrs) = param_attrs(cx.tcx, did, i) {
+                        self.check_fn_store(cx, cx.tables, a, attrs, "parameter");
+                    }
+                }
+            }
+            },
+        ExprKind::MethodCall(_, _, ref args) => {
+            if let Some(did) = cx.tables.type_dependent_def_id(ex.hir_id) {
+                for (i, a) in args.iter().enumerate() {
+                    if let Some(attrs) = param_attrs(cx.tcx, did, i) {
+                        self.check_fn_store(cx, cx.tables, a, attrs, "parameter");
+                    }
+                }
+            }
+            },
+        _ => {},
+        }
+    }
 }
 
 impl Pass
@@ -92,97 +292,529 @@ impl Pass
     fn fill_cache_for(&mut self, tcx: &TyCtxt, node_id: hir::HirId)
     {
         debug!("Filling cache for node {:?}", node_id);
-        let attrs = tcx.hir().attrs(node_id);
         let mut lh = ::database::CACHE.write().unwrap();
-        for tag_name in Iterator::chain( get_tags(attrs, self.sym_issafe.clone()), get_tags(attrs, self.sym_reqsafe.clone()) )
+        // The function's own tags take priority over those on an enclosing `extern { }` block
+        for attrs in Some(tcx.hir().attrs(node_id)).into_iter().chain(foreign_block_attrs(*tcx, node_id))
         {
-            debug!("#[is_safe/req_safe] {} - {}", tag_name, node_id);
-            let tag = lh.get_tag_or_add(&tag_name.as_str());
-            lh.mark(node_id, tag,  true);
+            for tag_name in Iterator::chain( get_tags(attrs, self.sym_issafe.clone()), get_tags(attrs, self.sym_reqsafe.clone()) )
+            {
+                debug!("#[is_safe/req_safe] {} - {}", tag_name, node_id);
+                let tag = lh.get_tag_or_add(&tag_name.as_str());
+                lh.mark(node_id, tag,  true);
+            }
+            for (tag_name, span) in get_tag_spans(attrs, self.sym_notsafe.clone())
+            {
+                debug!("#[not_safe] {} - {}", tag_name, node_id);
+                let tag = lh.get_tag_or_add(&tag_name.as_str());
+                lh.mark(node_id, tag,  false);
+                lh.set_reason(node_id, tag, Reason::Attribute(span));
+            }
         }
-        for tag_name in get_tags(attrs, self.sym_notsafe.clone())
+    }
+
+    /// Check that methods in a trait impl are safe for the tags declared on the trait's methods
+    ///
+    /// A trait method with `#[req_safe(tag)]` or `#[is_safe(tag)]` requires all implementations to be safe for
+    /// that tag, including impls that use the default body.
+    fn check_trait_impl(&mut self, cx: &lint::LateContext, item: &hir::Item, trait_did: DefId, impl_items: &[hir::ImplItemRef])
+    {
+        let tcx = cx.tcx;
+        let impl_did = tcx.hir().local_def_id(item.hir_id);
+        for trait_item in tcx.associated_items(trait_did)
         {
-            debug!("#[not_safe] {} - {}", tag_name, node_id);
-            let tag = lh.get_tag_or_add(&tag_name.as_str());
-            lh.mark(node_id, tag,  false);
+            if trait_item.kind != ty::AssocKind::Method {
+                continue ;
+            }
+            let attrs = tcx.get_attrs(trait_item.def_id);
+            let tags: Vec<_> = Iterator::chain( get_tags(&attrs, self.sym_reqsafe), get_tags(&attrs, self.sym_issafe) ).collect();
+            if tags.is_empty() {
+                continue ;
+            }
+            let impl_item = impl_items.iter().find(|r| r.ident.name == trait_item.ident.name);
+            for tag_name in tags
+            {
+                let tag = ::database::CACHE.write().unwrap().get_tag_or_add(&tag_name.as_str());
+                let (is_safe, span, desc, method_did) = match impl_item
+                    {
+                    Some(r) => {
+                        let did = tcx.hir().local_def_id(r.id.hir_id);
+                        (self.method_is_safe(cx, did, tag), r.span, "Implementation of", Some(did))
+                        },
+                    // Inherited default body, check it with `Self` set to this impl's type
+                    None => match tcx.hir().as_local_hir_id(trait_item.def_id).map(|id| tcx.hir().get(id))
+                        {
+                        Some(hir::Node::TraitItem(&hir::TraitItem { kind: hir::TraitItemKind::Method(_, hir::TraitMethod::Provided(body)), .. })) => {
+                            let trait_substs = tcx.impl_trait_ref(impl_did).expect("Trait impl without a trait").substs;
+                            let substs = InternalSubsts::identity_for_item(tcx, trait_item.def_id).rebase_onto(tcx, trait_did, trait_substs);
+                            let param_env = tcx.param_env(impl_did).with_reveal_all();
+                            let mut is_safe = true;
+                            {
+                                let mut v = Visitor::new(self, cx, body, tag, |_: &Violation| { is_safe = false; }).with_instance(param_env, substs);
+                                hir::intravisit::Visitor::visit_body(&mut v, tcx.hir().body(body));
+                            }
+                            (is_safe, item.span, "Inherited default body of", None)
+                            },
+                        // Extern (or required) trait methods - the default body can't be checked here
+                        _ => (true, item.span, "", None),
+                        },
+                    };
+                if !is_safe {
+                    let mut db = match tag_diagnostic(cx, tag, span,
+                            &format!("{} `{}` is not {}-safe, but the trait requires it", desc, trait_item.ident, tag_name)
+                            )
+                        {
+                        Some(v) => v,
+                        None => continue,
+                        };
+                    db.span_note(tcx.def_span(trait_item.def_id), &format!("`{}` is declared {}-safe here", trait_item.ident, tag_name));
+                    if let Some(did) = method_did {
+                        explain_chain(&mut db, tcx, did, tag, &tag_name.as_str());
+                    }
+                    db.emit();
+                }
+            }
         }
     }
 
-    /// Recursively check that the provided function is either safe or unsafe.
+    /// Recursively check that the provided function is either safe or unsafe (returning why it isn't safe)
     // Used to avoid excessive annotating
-    fn recurse_fcn_body(&mut self, cx: &lint::LateContext, node_id: hir::HirId, tag: ::database::Tag) -> bool
+    fn recurse_fcn_body(&mut self, cx: &lint::LateContext, node_id: hir::HirId, tag: ::database::Tag) -> Result<(), Reason>
     {
+        if ::database::CACHE.read().unwrap().backend() == ::database::Backend::Mir {
+            let did = cx.tcx.hir().local_def_id(node_id);
+            if cx.tcx.is_mir_available(did) {
+                return ::mir::item_is_safe(self, cx, did, tag);
+            }
+        }
         // and apply a visitor to all 
         match cx.tcx.hir().get(node_id)
         {
         hir::Node::Item(i) =>
             match i.kind
 			{
-            ItemKind::Fn(_, _, _, ref body) => {
-                // Enumerate this function's code, recursively checking for a call to an unsafe method
-                let mut is_safe = true;
-                {
-                    let mut v = Visitor {
-                        pass: self, cx: cx, tag: tag,
-                        cb: |_| { is_safe = false; }
-                        };
-                    hir::intravisit::walk_body(&mut v, cx.tcx.hir().body(*body));
-                }
-                is_safe
-                },
+            ItemKind::Fn(_, _, _, body) => self.body_is_safe(cx, body, tag),
             ref v @ _ => {
                 error!("Node ID {} points to a non-function item {:?}", node_id, v);
-                true
+                Ok( () )
                 },
             },
         hir::Node::ImplItem(i) =>
             match i.kind
 			{
-            hir::ImplItemKind::Method(_, ref body) => {
-                
-                let mut is_safe = true;
-                {
-                    let mut v = Visitor {
-                        pass: self, cx: cx, tag: tag,
-                        cb: |_| { is_safe = false; }
-                        };
-                    hir::intravisit::walk_body(&mut v, cx.tcx.hir().body(*body));
-                }
-                is_safe
-                },
-            _ => true,
+            hir::ImplItemKind::Method(_, body) => self.body_is_safe(cx, body, tag),
+            _ => Ok( () ),
+            },
+        // Provided trait methods are reached when an impl doesn't override them
+        hir::Node::TraitItem(i) =>
+            match i.kind
+            {
+            hir::TraitItemKind::Method(_, hir::TraitMethod::Provided(body)) => self.body_is_safe(cx, body, tag),
+            _ => Ok( () ),
+            },
+        // Closures converted to function pointers
+        hir::Node::Expr(e) =>
+            match e.kind
+            {
+            ExprKind::Closure(_, _, body, _, _) => self.body_is_safe(cx, body, tag),
+            _ => Ok( () ),
+            },
+        // Untagged functions declared in an `extern { }` block (tagged ones are handled by `fill_cache_for`)
+        hir::Node::ForeignItem(_) =>
+            if ::database::CACHE.read().unwrap().tag_options(tag).ffi_unsafe {
+                Err(Reason::UntaggedFfi)
+            }
+            else {
+                Ok( () )
             },
-        //hir::map::NodeForeignItem(i) =>
-        //    if Self::check_for_marker(tcx, i.id, "tag_safe", name) {
-        //        true
-        //    }
-        //    else if Self::check_for_marker(tcx, i.id, "tag_unsafe", name) {
-        //        false
-        //    }
-        //    else {
-        //        unknown_assume
-        //    },
         ref v @ _ => {
             error!("Node ID {} points to non-item {:?}", node_id, v);
-            true
+            Ok( () )
             }
         }
     }
+
+    /// Enumerate a function's code, recursively checking for a call to an unsafe method
+    fn body_is_safe(&mut self, cx: &lint::LateContext, body: hir::BodyId, tag: ::database::Tag) -> Result<(), Reason>
+    {
+        let mut reason = None;
+        {
+            let mut v = Visitor::new(self, cx, body, tag, |v: &Violation| if reason.is_none() { reason = Some(v.reason()); });
+            hir::intravisit::Visitor::visit_body(&mut v, cx.tcx.hir().body(body));
+        }
+        match reason
+        {
+        Some(r) => Err(r),
+        None => Ok( () ),
+        }
+    }
+
+    /// Get the tag explicitly applied to a function (ignoring the contents of the function)
+    ///
+    /// For extern functions, attributes in the crate's metadata take priority over any loaded tag list.
+    pub fn explicit_tag(&self, tcx: &TyCtxt, id: DefId, tag: ::database::Tag) -> Option<bool>
+    {
+        let lh = ::database::CACHE.read().unwrap();
+        match tcx.hir().as_local_hir_id(id)
+        {
+        None => self.attrs_tag(&lh, &tcx.get_attrs(id), tag)
+            .or_else(|| lh.get_extern(tcx, id.krate, id.index, tag)),
+        Some(node_id) => self.attrs_tag(&lh, tcx.hir().attrs(node_id), tag)
+            .or_else(|| foreign_block_attrs(*tcx, node_id).and_then(|attrs| self.attrs_tag(&lh, attrs, tag))),
+        }
+    }
+
+    /// Get the names of every tag in a function's attributes
+    pub fn attr_tag_names(&self, attrs: &[ast::Attribute]) -> Vec<Symbol>
+    {
+        get_tags(attrs, self.sym_issafe.clone())
+            .chain( get_tags(attrs, self.sym_notsafe.clone()) )
+            .chain( get_tags(attrs, self.sym_reqsafe.clone()) )
+            .collect()
+    }
+
+    /// Get the status set for a tag by a function's attributes
+    fn attrs_tag(&self, lh: &::database::StaticCache, attrs: &[ast::Attribute], tag: ::database::Tag) -> Option<bool>
+    {
+        let is_tag = |name: Symbol| lh.get_tag_opt(&name.as_str()) == Some(tag);
+        if get_tags(attrs, self.sym_notsafe).any(&is_tag) {
+            Some(false)
+        }
+        else if Iterator::chain( get_tags(attrs, self.sym_issafe), get_tags(attrs, self.sym_reqsafe) ).any(&is_tag) {
+            Some(true)
+        }
+        else {
+            None
+        }
+    }
+
+    /// Determine if `id` (with the provided generic parameters) is safe, after resolving it to the concrete function
+    fn instance_is_safe<'tcx>(&mut self, cx: &lint::LateContext<'_,'tcx>, param_env: ty::ParamEnv<'tcx>, id: DefId, substs: SubstsRef<'tcx>, tag: ::database::Tag) -> bool
+    {
+        let substs = cx.tcx.erase_regions(&substs);
+        // Resolve to the function that's actually called (e.g. the impl method instead of the trait method)
+        match ty::Instance::resolve(cx.tcx, param_env, id, substs)
+        {
+        Some(instance) =>
+            match instance.def
+            {
+            ty::InstanceDef::Item(did) => self.method_is_safe(cx, did, tag),
+            // Call through a `dyn Trait`
+            ty::InstanceDef::Virtual(did, _) => self.virtual_is_safe(cx, did, tag),
+            // Intrinsics (e.g. `transmute`) are expanded inline by the compiler (same as the MIR backend)
+            ty::InstanceDef::Intrinsic(_) => true,
+            // `Clone` for tuples, arrays and closures, which clones each component
+            ty::InstanceDef::CloneShim(clone_did, ty) => self.clone_is_safe(cx, param_env, clone_did, ty, tag),
+            // `ptr::drop_in_place`
+            ty::InstanceDef::DropGlue(_, None) => true,
+            ty::InstanceDef::DropGlue(_, Some(ty)) => self.unsafe_drop(cx, param_env, ty, tag).is_none(),
+            // Calls through the `Fn*` traits to a function item, pointer, or closure
+            ty::InstanceDef::FnPtrShim(_, ty) => self.callee_ty_is_safe(cx, param_env, ty, tag),
+            ty::InstanceDef::ClosureOnceShim { .. } => self.callee_ty_is_safe(cx, param_env, instance.substs.type_at(0), tag),
+            // Other shims (e.g. for calling a by-value `self` method through a vtable) call the item directly
+            ref d @ _ => {
+                debug!("Call to {:?} resolved to a shim {:?}", id, d);
+                self.method_is_safe(cx, d.def_id(), tag)
+                },
+            },
+        None => self.unresolved_is_safe(cx, id, tag),
+        }
+    }
+
+    /// Determine if calling a value of type `ty` (a function item, function pointer or closure) is safe
+    fn callee_ty_is_safe<'tcx>(&mut self, cx: &lint::LateContext<'_,'tcx>, param_env: ty::ParamEnv<'tcx>, ty: Ty<'tcx>, tag: ::database::Tag) -> bool
+    {
+        match ty.kind
+        {
+        ty::FnDef(did, substs) => self.instance_is_safe(cx, param_env, did, substs, tag),
+        ty::Closure(did, _) => self.method_is_safe(cx, did, tag),
+        // The origin of the pointer isn't known here
+        ty::FnPtr(_) => !::database::CACHE.read().unwrap().tag_options(tag).fn_pointers,
+        _ => {
+            info!("Call through a shim for unexpected type {:?}", ty);
+            false
+            },
+        }
+    }
+
+    /// Determine if the `Clone` shim for a tuple, array or closure is safe (i.e. cloning each component is safe)
+    fn clone_is_safe<'tcx>(&mut self, cx: &lint::LateContext<'_,'tcx>, param_env: ty::ParamEnv<'tcx>, clone_did: DefId, ty: Ty<'tcx>, tag: ::database::Tag) -> bool
+    {
+        let tcx = cx.tcx;
+        let components: Vec<Ty<'tcx>> = match ty.kind
+            {
+            ty::Tuple(..) => ty.tuple_fields().collect(),
+            ty::Array(inner, _) => vec![inner],
+            ty::Closure(did, substs) => substs.as_closure().upvar_tys(did, tcx).collect(),
+            // Other types are `Copy`
+            _ => Vec::new(),
+            };
+        components.into_iter()
+            // `Copy` types are cloned by copying
+            .filter(|t| !t.is_copy_modulo_regions(tcx, param_env, DUMMY_SP))
+            .all(|t| self.instance_is_safe(cx, param_env, clone_did, tcx.mk_substs_trait(t, &[]), tag))
+    }
+
+    /// Get the first destructor that isn't safe, of those that run when a value of type `ty` is dropped
+    pub fn unsafe_drop<'tcx>(&mut self, cx: &lint::LateContext<'_,'tcx>, param_env: ty::ParamEnv<'tcx>, ty: Ty<'tcx>, tag: ::database::Tag) -> Option<DefId>
+    {
+        let mut seen = Vec::new();
+        self.unsafe_drop_inner(cx, param_env, ty, tag, &mut seen)
+    }
+    fn unsafe_drop_inner<'tcx>(&mut self, cx: &lint::LateContext<'_,'tcx>, param_env: ty::ParamEnv<'tcx>, ty: Ty<'tcx>, tag: ::database::Tag, seen: &mut Vec<Ty<'tcx>>) -> Option<DefId>
+    {
+        let tcx = cx.tcx;
+        if seen.contains(&ty) || !ty.needs_drop(tcx, param_env) {
+            return None;
+        }
+        seen.push(ty);
+        match ty.kind
+        {
+        _ if ty.is_box() => self.unsafe_drop_inner(cx, param_env, ty.boxed_ty(), tag, seen),
+        ty::Adt(adt, substs) => {
+            if let Some(dtor) = tcx.adt_destructor(adt.did) {
+                if !self.method_is_safe(cx, dtor.did, tag) {
+                    return Some(dtor.did);
+                }
+            }
+            if adt.is_union() {
+                return None;
+            }
+            adt.all_fields().filter_map(|field| self.unsafe_drop_inner(cx, param_env, field.ty(tcx, substs), tag, seen)).next()
+            },
+        ty::Tuple(..) => ty.tuple_fields().filter_map(|t| self.unsafe_drop_inner(cx, param_env, t, tag, seen)).next(),
+        ty::Closure(did, substs) => substs.as_closure().upvar_tys(did, tcx).filter_map(|t| self.unsafe_drop_inner(cx, param_env, t, tag, seen)).next(),
+        ty::Array(inner, _) | ty::Slice(inner) => self.unsafe_drop_inner(cx, param_env, inner, tag, seen),
+        // Generic parameters and trait objects can't be known here
+        _ => {
+            debug!("Drop of {:?} not checked", ty);
+            None
+            },
+        }
+    }
+
+    /// Check if a slot (field, static or parameter) holding a function pointer is tagged safe
+    fn slot_is_safe(&self, attrs: &[ast::Attribute], tag: ::database::Tag) -> bool
+    {
+        let lh = ::database::CACHE.read().unwrap();
+        Iterator::chain( get_tags(attrs, self.sym_issafe), get_tags(attrs, self.sym_reqsafe) )
+            .any(|name| lh.get_tag_opt(&name.as_str()) == Some(tag))
+    }
+
+    /// Check a function (or closure) being stored as a pointer in a slot tagged with `#[req_safe(tag)]`
+    fn check_fn_store<'tcx>(&mut self, cx: &lint::LateContext<'_,'tcx>, tables: &ty::TypeckTables<'tcx>, value: &hir::Expr, slot_attrs: &[ast::Attribute], slot_desc: &str)
+    {
+        // Arrays of function pointers (e.g. dispatch tables)
+        if let ExprKind::Array(ref values) = value.kind {
+            for v in values.iter() {
+                self.check_fn_store(cx, tables, v, slot_attrs, slot_desc);
+            }
+            return ;
+        }
+        let tags: Vec<_> = Iterator::chain( get_tags(slot_attrs, self.sym_issafe), get_tags(slot_attrs, self.sym_reqsafe) ).collect();
+        for tag_name in tags
+        {
+            let tag = {
+                let mut lh = ::database::CACHE.write().unwrap();
+                let tag = lh.get_tag_or_add(&tag_name.as_str());
+                if !lh.tag_options(tag).fn_pointers {
+                    continue ;
+                }
+                tag
+                };
+            let param_env = cx.param_env.with_reveal_all();
+            let (is_safe, callee) = match tables.expr_ty(value).kind
+                {
+                ty::FnDef(did, substs) => (self.instance_is_safe(cx, param_env, did, substs, tag), Some(resolve_item(cx.tcx, param_env, did, substs))),
+                ty::Closure(did, _) => (self.method_is_safe(cx, did, tag), Some(did)),
+                // Already a pointer, which must have been read from another slot with this tag
+                ty::FnPtr(_) => {
+                    let is_safe = match slot_attrs(cx.tcx, tables, value)
+                        {
+                        Some(attrs) => self.slot_is_safe(&attrs, tag),
+                        None => false,
+                        };
+                    (is_safe, None)
+                    },
+                _ => continue,
+                };
+            if !is_safe {
+                let msg = match callee
+                    {
+                    Some(_) => format!("Storing a {0}-unsafe function in a #[req_safe({0})] {1}", tag_name, slot_desc),
+                    None => format!("Storing an untagged function pointer in a #[req_safe({0})] {1}", tag_name, slot_desc),
+                    };
+                let mut db = match tag_diagnostic(cx, tag, value.span, &msg)
+                    {
+                    Some(v) => v,
+                    None => continue,
+                    };
+                if let Some(callee) = callee {
+                    explain_chain(&mut db, cx.tcx, callee, tag, &tag_name.as_str());
+                }
+                db.emit();
+            }
+        }
+    }
+
+    /// Determine if a virtual call (via a trait object) to the trait method `id` is safe
+    ///
+    /// If the trait method is tagged, that tag is used. Otherwise (unless the tag requires virtual calls to be tagged)
+    /// every visible implementation of the method must be safe.
+    pub fn virtual_is_safe(&mut self, cx: &lint::LateContext, id: DefId, tag: ::database::Tag) -> bool
+    {
+        if let Some(v) = self.explicit_tag(&cx.tcx, id, tag) {
+            debug!("{:?} - {} (virtual, tagged)", id, v);
+            return v;
+        }
+        if ::database::CACHE.read().unwrap().tag_options(tag).strict_virtual {
+            debug!("{:?} - false (virtual, untagged)", id);
+            return false;
+        }
+
+        let tcx = cx.tcx;
+        let trait_did = tcx.trait_of_item(id).expect("Virtual call to a non-trait method");
+        let name = tcx.item_name(id);
+        let mut impls = Vec::new();
+        tcx.for_each_impl(trait_did, |impl_did| impls.push(impl_did));
+        for impl_did in impls
+        {
+            // Impls that don't override the method use the default body
+            let method_did = tcx.associated_items(impl_did)
+                .find(|item| item.ident.name == name)
+                .map(|item| item.def_id)
+                .unwrap_or(id);
+            if !self.method_is_safe(cx, method_did, tag) {
+                debug!("{:?} - false (virtual, {:?} not safe)", id, method_did);
+                return false;
+            }
+        }
+        true
+    }
+
+    /// Determine if an extern function without any tag information is safe, using the tag's `extern_policy`
+    fn unknown_extern_is_safe(&mut self, id: DefId, tag: ::database::Tag) -> bool
+    {
+        let policy = ::database::CACHE.read().unwrap().tag_options(tag).extern_policy;
+        debug!("{:?} - {:?} (extern assumed)", id, policy);
+        match policy
+        {
+        ExternPolicy::AssumeSafe => true,
+        ExternPolicy::AssumeUnsafe => false,
+        ExternPolicy::Warn => {
+            // Only reported once, by the first check of a linted function that uses it
+            if !self.assumed_externs.contains(&(id, tag)) && !self.pending_assumptions.contains(&(id, tag)) {
+                self.pending_assumptions.push( (id, tag) );
+            }
+            true
+            },
+        }
+    }
+
+    /// Get the position in the list of newly assumed extern functions, to pass to `take_assumptions`
+    pub fn assumption_mark(&self) -> usize
+    {
+        self.pending_assumptions.len()
+    }
+    /// Take the extern functions that have been assumed to be safe since `assumption_mark` was called (which the
+    /// caller reports)
+    pub fn take_assumptions(&mut self, mark: usize) -> Vec<(DefId, ::database::Tag)>
+    {
+        let rv: Vec<_> = self.pending_assumptions.drain(mark..).collect();
+        // Earlier ones were assumed outside of a check of a linted function (e.g. while checking a trait impl). They're
+        // dropped instead of being reported against an unrelated call, and are still reported if found again.
+        self.pending_assumptions.clear();
+        self.assumed_externs.extend(rv.iter().cloned());
+        rv
+    }
+
+    /// Determine if a call that couldn't be resolved to a concrete function is safe
+    ///
+    /// This is a generic call (e.g. `T::method()`), so only an explicit tag on the method is trusted.
+    pub fn unresolved_is_safe(&mut self, cx: &lint::LateContext, id: DefId, tag: ::database::Tag) -> bool
+    {
+        match self.explicit_tag(&cx.tcx, id, tag)
+        {
+        Some(v) => {
+            debug!("{:?} - {} (unresolved, tagged)", id, v);
+            v
+            },
+        None => {
+            debug!("{:?} - false (unresolved, untagged)", id);
+            false
+            },
+        }
+    }
     
+    /// Check a function that hasn't been seen yet (using Tarjan's SCC algorithm)
+    ///
+    /// Any unsafe call within a cycle propagates back to the SCC's root (via the return values), so the
+    /// root's result applies to every member of the SCC.
+    fn scc_visit(&mut self, cx: &lint::LateContext, node_id: hir::HirId, tag: ::database::Tag) -> bool
+    {
+        let index = self.next_index;
+        self.next_index += 1;
+        self.scc_stack.push( (node_id, tag) );
+        self.scc_index.insert( (node_id, tag), index );
+        self.visit_stack.push(VisitFrame { index: index, lowlink: index });
+
+        let rv = match self.recurse_fcn_body(cx, node_id, tag)
+            {
+            Ok(()) => true,
+            Err(reason) => {
+                ::database::CACHE.write().unwrap().set_reason(node_id, tag, reason);
+                false
+                },
+            };
+
+        let frame = self.visit_stack.pop().expect("Visit stack underflow");
+        if frame.lowlink == index {
+            // Root of an SCC, all members take this result
+            let root = cx.tcx.hir().local_def_id(node_id);
+            let mut lh = ::database::CACHE.write().unwrap();
+            loop
+            {
+                let member = self.scc_stack.pop().expect("SCC stack underflow");
+                self.scc_index.remove(&member);
+                debug!("{} - {} (recursed)", member.0, rv);
+                lh.mark(member.0, tag, rv);
+                if !rv {
+                    // Members that only call back into the cycle don't have their own reason
+                    lh.set_reason(member.0, tag, Reason::Cycle(root));
+                }
+                if member.0 == node_id {
+                    break;
+                }
+            }
+        }
+        else {
+            // Part of a larger SCC, the result is stored when the root completes
+            debug!("{} - {} (pending SCC)", node_id, rv);
+            if let Some(top) = self.visit_stack.last_mut() {
+                top.lowlink = ::std::cmp::min(top.lowlink, frame.lowlink);
+            }
+        }
+        rv
+    }
+
     /// Locate a #[tag_safe(<name>)] attribute on the passed item
     pub fn method_is_safe(&mut self, cx: &lint::LateContext, id: DefId, tag: ::database::Tag) -> bool
     {
         match cx.tcx.hir().as_local_hir_id(id)
         {
         None => {
-            // TODO: Get the entry from the crate cache
-            if let Some(v) = ::database::CACHE.read().unwrap().get_extern(&cx.tcx,id.krate, id.index, tag) {
-                debug!("{:?} - {} (extern cached)", id, v);
+            // Attributes from the crate's metadata, or the loaded tag list
+            if let Some(v) = self.explicit_tag(&cx.tcx, id, tag) {
+                debug!("{:?} - {} (extern tagged)", id, v);
                 v
             }
+            else if cx.tcx.is_foreign_item(id) {
+                // FFI function declared in another crate
+                let is_safe = !::database::CACHE.read().unwrap().tag_options(tag).ffi_unsafe;
+                debug!("{:?} - {} (extern FFI)", id, is_safe);
+                is_safe
+            }
             else {
-                debug!("{:?} - {} (extern assumed)", id, true);
-                true
+                self.unknown_extern_is_safe(id, tag)
             }
             },
         Some(node_id) => {
@@ -196,45 +828,263 @@ impl Pass
                 debug!("{} - {} (cached)", node_id, v);
                 v
             }
-            else {
-                // If this node is currently being checked, assume it's valid.
-                // TODO: This can lead to a false positive being stored.
-                if self.visit_stack.iter().position(|x| *x == node_id).is_some() {
-                    warn!("Recursion, assuming true");
-                    true
-                }
-                else {
-                    self.visit_stack.push(node_id);
-                    let rv = self.recurse_fcn_body(cx, node_id, tag);
-                    self.visit_stack.pop();
-                    
-                    debug!("{} - {} (recursed)", node_id, rv);
-                    ::database::CACHE.write().unwrap().mark(node_id, tag,  rv);
-                    rv
+            else if let Some(&index) = self.scc_index.get(&(node_id, tag)) {
+                // Recursion: this node is part of an SCC that is still being checked. Assume it's safe for now,
+                // the result for the whole SCC is only stored once the root of the SCC has been checked.
+                debug!("{} - recursion, deferring to SCC root", node_id);
+                if let Some(top) = self.visit_stack.last_mut() {
+                    top.lowlink = ::std::cmp::min(top.lowlink, index);
                 }
+                true
+            }
+            else {
+                self.scc_visit(cx, node_id, tag)
             }
             }
         }
     }
 }
 
-struct Visitor<'a, 'tcx: 'a, F: FnMut(&Span) + 'a>
+struct Visitor<'a, 'tcx: 'a, F: FnMut(&Violation) + 'a>
 {
     pass: &'a mut Pass,
 	cx: &'a lint::LateContext<'a,'tcx>,
+    // Type tables for the body being visited (which isn't always the one the lint context is in)
+    tables: &'a ty::TypeckTables<'tcx>,
+    // Environment of the body's owner, used to resolve trait calls
+    param_env: ty::ParamEnv<'tcx>,
+    // Generic parameters to apply to the body (when checking a specific instance of a generic body)
+    instance_substs: Option<SubstsRef<'tcx>>,
     tag: ::database::Tag,
+    // Set for the function being linted, where assumed extern functions are reported
+    root: bool,
     cb: F,
 }
 
-impl<'a, 'tcx: 'a, F: FnMut(&Span)> hir::intravisit::Visitor<'a> for Visitor<'a, 'tcx, F>
+impl<'a, 'tcx: 'a, F: FnMut(&Violation)> Visitor<'a, 'tcx, F>
+{
+    fn new(pass: &'a mut Pass, cx: &'a lint::LateContext<'a,'tcx>, body: hir::BodyId, tag: ::database::Tag, cb: F) -> Self
+    {
+        let owner = cx.tcx.hir().body_owner_def_id(body);
+        Visitor {
+            pass: pass, cx: cx, tag: tag,
+            tables: cx.tcx.body_tables(body),
+            param_env: cx.tcx.param_env(owner).with_reveal_all(),
+            instance_substs: None,
+            root: false,
+            cb: cb,
+            }
+    }
+    /// Mark as visiting the function being linted (instead of one it calls)
+    fn as_root(self) -> Self
+    {
+        Visitor {
+            root: true,
+            ..self
+            }
+    }
+    /// Check the body as the provided instance, instead of generically
+    fn with_instance(self, param_env: ty::ParamEnv<'tcx>, substs: SubstsRef<'tcx>) -> Self
+    {
+        Visitor {
+            param_env: param_env,
+            instance_substs: Some(substs),
+            ..self
+            }
+    }
+
+    /// Run a check, reporting any extern functions that it assumed were safe if this is the function being linted
+    fn checked<T, C: FnOnce(&mut Pass) -> T>(&mut self, span: &Span, check: C) -> T
+    {
+        if !self.root {
+            // Left for the function being linted to report
+            return check(self.pass);
+        }
+        let mark = self.pass.assumption_mark();
+        let rv = check(self.pass);
+        let assumed = self.pass.take_assumptions(mark);
+        report_assumed(self.cx, report_span(self.cx.tcx, span, None).0, &assumed);
+        rv
+    }
+
+    /// Report a call to a non-safe function (or another non-safe operation, described by `what`)
+    fn report(&mut self, span: &Span, callee: Option<DefId>, what: &str)
+    {
+        let (span, note) = report_span(self.cx.tcx, span, callee);
+        (self.cb)(&Violation { span: span, callee: callee, what: what, note: note.as_ref().map(|v| &v[..]) });
+    }
+
+    /// Check a call to `id` (with the provided generic parameters)
+    fn check_call(&mut self, span: &Span, id: DefId, substs: SubstsRef<'tcx>)
+    {
+        let tcx = self.cx.tcx;
+        let substs = match self.instance_substs
+            {
+            Some(instance_substs) => substs.subst(tcx, instance_substs),
+            None => substs,
+            };
+        let (cx, param_env, tag) = (self.cx, self.param_env, self.tag);
+        let is_safe = self.checked(span, |pass| pass.instance_is_safe(cx, param_env, id, substs, tag));
+        if !is_safe {
+            let callee = resolve_item(tcx, self.param_env, id, substs);
+            self.report(span, Some(callee), "call");
+        }
+        else {
+            debug!("Safe call {:?}", id);
+        }
+    }
+}
+
+impl<'a, 'tcx: 'a, F: FnMut(&Violation)> Visitor<'a, 'tcx, F>
+{
+    /// Check a call where the callee is a value (instead of a path to a function)
+    fn check_value_call(&mut self, ex: &hir::Expr, fcn: &hir::Expr)
+    {
+        match self.tables.expr_ty(fcn).kind
+        {
+        // A function item used as a value (e.g. `let f = foo; f()`)
+        ty::FnDef(did, substs) => self.check_call(&ex.span, did, substs),
+        // Call through a function pointer, only allowed if the pointer came from a tagged slot
+        ty::FnPtr(_) if ::database::CACHE.read().unwrap().tag_options(self.tag).fn_pointers => {
+            let is_safe = match slot_attrs(self.cx.tcx, self.tables, fcn)
+                {
+                Some(attrs) => self.pass.slot_is_safe(&attrs, self.tag),
+                None => false,
+                };
+            if !is_safe {
+                self.report(&ex.span, None, "call through an untagged function pointer");
+            }
+            },
+        ty::FnPtr(_) => {},
+        // Closure bodies are checked as part of the function that creates them
+        ty::Closure(..) => {},
+        // Overloaded call through the `Fn*` traits (e.g. on a `Box<dyn Fn()>`)
+        _ => match self.tables.type_dependent_def_id(ex.hir_id)
+            {
+            Some(id) => {
+                let tcx = self.cx.tcx;
+                let substs = self.tables.node_substs(ex.hir_id);
+                if is_generic_callable_call(tcx, id, substs) {
+                    debug!("Call through a generic callable, checked where it was created");
+                    return ;
+                }
+                self.check_call(&ex.span, id, tcx.mk_substs_trait(callable_self_ty(substs.type_at(0)), &substs[1..]));
+                },
+            None => info!("Call without a known callee"),
+            },
+        }
+    }
+
+    /// Check an expression that calls a method without explicit call syntax (e.g. an overloaded operator)
+    fn check_method_call(&mut self, span: &Span, ex: &hir::Expr)
+    {
+        match self.tables.type_dependent_def_id(ex.hir_id)
+        {
+        Some(id) => self.check_call(span, id, self.tables.node_substs(ex.hir_id)),
+        None => info!("Overloaded operator with unknown callee"),
+        }
+    }
+
+    /// Check the `Future::poll` call made by an `.await`
+    fn check_await(&mut self, ex: &hir::Expr, future: &hir::Expr)
+    {
+        let tcx = self.cx.tcx;
+        let poll = tcx.lang_items().future_trait()
+            .and_then(|trait_did| tcx.associated_items(trait_did).find(|i| i.ident.as_str() == "poll"))
+            .map(|i| i.def_id);
+        match poll
+        {
+        Some(poll) => {
+            let substs = tcx.mk_substs_trait(self.tables.expr_ty(future), &[]);
+            let substs = match self.instance_substs
+                {
+                Some(instance_substs) => substs.subst(tcx, instance_substs),
+                None => substs,
+                };
+            let (cx, param_env, tag) = (self.cx, self.param_env, self.tag);
+            if !self.checked(&ex.span, |pass| pass.instance_is_safe(cx, param_env, poll, substs, tag)) {
+                // The desugared match has the span of the original `.await` expression
+                let note = format!("`.await` calls `{}`", tcx.def_path_str(poll));
+                let callee = resolve_item(tcx, self.param_env, poll, substs);
+                (self.cb)(&Violation { span: ex.span, callee: Some(callee), what: "call", note: Some(&note) });
+            }
+            },
+        None => info!("No `Future` lang item, can't check `.await`"),
+        }
+    }
+
+    /// Check implicit `Deref::deref` calls inserted by auto-deref
+    fn check_adjustments(&mut self, ex: &hir::Expr)
+    {
+        let tables = self.tables;
+        let mut source = tables.expr_ty(ex);
+        for adj in tables.expr_adjustments(ex)
+        {
+            if let Adjust::Deref(Some(ref overloaded)) = adj.kind {
+                let (id, substs) = overloaded.method_call(self.cx.tcx, source);
+                self.check_call(&ex.span, id, substs);
+            }
+            source = adj.target;
+        }
+    }
+
+    /// Check the `Drop` impls that run when a value of type `ty` is dropped
+    fn check_drop(&mut self, span: &Span, ty: Ty<'tcx>)
+    {
+        let (cx, param_env, tag) = (self.cx, self.param_env, self.tag);
+        if let Some(dtor) = self.checked(span, |pass| pass.unsafe_drop(cx, param_env, ty, tag)) {
+            self.report(span, Some(dtor), "drop");
+        }
+    }
+}
+
+impl<'a, 'tcx: 'a, F: FnMut(&Violation)> hir::intravisit::Visitor<'a> for Visitor<'a, 'tcx, F>
 {
 	fn nested_visit_map<'this>(&'this mut self) -> hir::intravisit::NestedVisitorMap<'this, 'a> {
-		hir::intravisit::NestedVisitorMap::None
+		// Closure and async bodies are visited as part of the enclosing function, so closures passed
+		// to other functions are treated as being executed by the caller.
+		hir::intravisit::NestedVisitorMap::OnlyBodies(&self.cx.tcx.hir())
 	}
 
+    // Values are dropped where the MIR drops them, which skips moved-out values and includes temporaries and the old
+    // value of an assigned place
+    fn visit_body(&mut self, b: &'a hir::Body) {
+        hir::intravisit::walk_body(self, b);
+        let tcx = self.cx.tcx;
+        let owner_id = tcx.hir().body_owner(b.id());
+        match tcx.hir().body_owner_kind(owner_id)
+        {
+        hir::BodyOwnerKind::Fn | hir::BodyOwnerKind::Closure => {},
+        // Constants can't run destructors
+        _ => return,
+        }
+        let owner = tcx.hir().local_def_id(owner_id);
+        // A closure has extra generic parameters after those of the function that it's defined in
+        let substs = self.instance_substs.map(|substs| InternalSubsts::identity_for_item(tcx, owner).rebase_onto(tcx, tcx.closure_base_def_id(owner), substs));
+        let body = tcx.optimized_mir(owner);
+        for bb in body.basic_blocks()
+        {
+            let term = bb.terminator();
+            match term.kind
+            {
+            mir::TerminatorKind::Drop { ref location, .. } | mir::TerminatorKind::DropAndReplace { ref location, .. } => {
+                let ty = location.ty(body, tcx).ty;
+                let ty = match substs
+                    {
+                    Some(substs) => tcx.subst_and_normalize_erasing_regions(substs, self.param_env, &ty),
+                    None => tcx.normalize_erasing_regions(self.param_env, ty),
+                    };
+                self.check_drop(&term.source_info.span, ty);
+                },
+            _ => {},
+            }
+        }
+    }
+
     // Locate function/method calls in a code block
     fn visit_expr(&mut self, ex: &'a hir::Expr) {
         debug!("visit node - {:?}", ex);
+        self.check_adjustments(ex);
         match ex.kind
         {
         // Call expressions - check that it's a path call
@@ -242,40 +1092,55 @@ impl<'a, 'tcx: 'a, F: FnMut(&Span)> hir::intravisit::Visitor<'a> for Visitor<'a,
 			match fcn.kind
 			{
 			ExprKind::Path(ref qp, ..) =>
-				match self.cx.tables.qpath_res(qp, fcn.hir_id)
+				match self.tables.qpath_res(qp, fcn.hir_id)
 				{
 				def::Res::Def(def::DefKind::Fn, did) | def::Res::Def(def::DefKind::Method, did) =>
 					// Check for a safety tag
-					if !self.pass.method_is_safe(self.cx, did, self.tag)
-					{
-						(self.cb)(&ex.span);
-					}
-					else {
-						debug!("Safe call {:?}", ex);
-					},
+					self.check_call(&ex.span, did, self.tables.node_substs(fcn.hir_id)),
+				// A local or static holding a function
+				def::Res::Local(_) | def::Res::Def(def::DefKind::Static, _) => self.check_value_call(ex, fcn),
 				_ => {
 					info!("Call ExprPath with an unknown Def type");
 					},
 				},
-			_ => {
-				info!("Call without ExprPath");
-				},
+			_ => self.check_value_call(ex, fcn),
 			},
         
         // Method call expressions - get the relevant method
         ExprKind::MethodCall(ref _id, ref _tys, ref _exprs) =>
-			match self.cx.tables.type_dependent_defs().get(ex.hir_id)
+			match self.tables.type_dependent_defs().get(ex.hir_id)
 			{
 			Some(Ok(callee)) => {
                 let id = callee.1;
                 
 				// Check for a safety tag
-				if !self.pass.method_is_safe(self.cx, id, self.tag) {
-					(self.cb)(&ex.span);
-				}
+				self.check_call(&ex.span, id, self.tables.node_substs(ex.hir_id));
 				},
 			_ => info!("ExprMethodCall with unknown callee"),
 			},
+
+        // Overloaded operators (e.g. `Add::add`, `Deref::deref` or `Index::index`)
+        ExprKind::Binary(ref op, ..) | ExprKind::AssignOp(ref op, ..) if self.tables.is_method_call(ex) =>
+            self.check_method_call(&op.span, ex),
+        ExprKind::Unary(..) | ExprKind::Index(..) if self.tables.is_method_call(ex) =>
+            self.check_method_call(&ex.span, ex),
+
+        // `.await` polls the future from within libstd, so check the `Future::poll` impl here
+        ExprKind::Match(ref future, _, hir::MatchSource::AwaitDesugar) => self.check_await(ex, future),
+
+        // `format_args!` passes the formatting trait methods (e.g. `Display::fmt`) as function pointers (other paths
+        // in the expansion are the callees of calls, which are checked as calls)
+        ExprKind::Path(ref qp) if is_format_args(&ex.span) && !is_callee(self.cx.tcx, ex) =>
+            if let def::Res::Def(def::DefKind::Method, did) = self.tables.qpath_res(qp, ex.hir_id) {
+                self.check_call(&ex.span, did, self.tables.node_substs(ex.hir_id));
+            },
+
+        // A function item passed to another function (e.g. for a generic `F: Fn()` parameter) is checked here, as
+        // closures are where they're created
+        ExprKind::Path(..) if is_call_argument(self.cx.tcx, ex) =>
+            if let ty::FnDef(did, substs) = self.tables.expr_ty_adjusted(ex).kind {
+                self.check_call(&ex.span, did, substs);
+            },
         
         // Ignore any other type of node
         _ => {},
@@ -284,9 +1149,293 @@ impl<'a, 'tcx: 'a, F: FnMut(&Span)> hir::intravisit::Visitor<'a> for Visitor<'a,
     }
 }
 
-fn get_tags<'a>(meta_items: &'a [ast::Attribute], attr_name: Symbol) -> impl Iterator<Item=::syntax::symbol::Symbol>+'a {
+/// Get the span to report a call against, and a note describing any hidden call
+///
+/// Calls generated by desugaring (e.g. `for` loops) or by `format_args!` are reported against the
+/// original source, with a note naming the hidden call.
+pub fn report_span(tcx: TyCtxt, span: &Span, callee: Option<DefId>) -> (Span, Option<String>)
+{
+    let (span, desc) = if let Some(kind) = span.desugaring_kind() {
+            let desc = match kind
+                {
+                DesugaringKind::ForLoop => "`for` loop",
+                DesugaringKind::QuestionMark => "`?` operator",
+                DesugaringKind::Await => "`.await`",
+                _ => "desugared expression",
+                };
+            (span.ctxt().outer_expn_data().call_site, Some(desc))
+        }
+        else if is_format_args(span) {
+            (span.source_callsite(), Some("formatting machinery"))
+        }
+        else {
+            (*span, None)
+        };
+    let note = match (desc, callee)
+        {
+        (Some(desc), Some(id)) => Some(format!("{} calls `{}`", desc, tcx.def_path_str(id))),
+        (Some(desc), None) => Some(format!("from {}", desc)),
+        (None, _) => None,
+        };
+    (span, note)
+}
+
+/// Start reporting a violation of `tag`, using the severity set for it (`None` if it's allowed)
+fn tag_diagnostic<'a>(cx: &'a lint::LateContext, tag: ::database::Tag, span: Span, msg: &str) -> Option<DiagnosticBuilder<'a>>
+{
+    let (tag_name, options) = {
+        let lh = ::database::CACHE.read().unwrap();
+        (lh.tag_name(tag).to_string(), lh.tag_options(tag))
+        };
+    let mut db = match options.severity
+        {
+        Severity::Allow => return None,
+        Severity::Lint => cx.struct_span_lint(NOT_TAGGED_SAFE, span, msg),
+        Severity::Deny => cx.sess().struct_span_err(span, msg),
+        };
+    if let Some(ref desc) = options.description {
+        db.note(&format!("`{}`: {}", tag_name, desc));
+    }
+    Some(db)
+}
+
+/// Report extern functions that were assumed to be safe (for tags with `extern_policy = "warn"`)
+pub fn report_assumed(cx: &lint::LateContext, span: Span, assumed: &[(DefId, ::database::Tag)])
+{
+    for &(id, tag) in assumed
+    {
+        let tag_name = ::database::CACHE.read().unwrap().tag_name(tag).to_string();
+        cx.struct_span_lint(TAG_SAFE_ASSUMED_EXTERN, span,
+                &format!("Assuming that extern function `{}` is {}-safe", cx.tcx.def_path_str(id), tag_name)
+                )
+            .note("it has no tag information - tag it, add it to a tag list, or set `extern_policy` for the tag")
+            .emit();
+    }
+}
+
+/// Get the function that a call to `id` resolves to, or `id` itself if it doesn't resolve to a single function
+pub fn resolve_item<'tcx>(tcx: TyCtxt<'tcx>, param_env: ty::ParamEnv<'tcx>, id: DefId, substs: SubstsRef<'tcx>) -> DefId
+{
+    match ty::Instance::resolve(tcx, param_env, id, tcx.erase_regions(&substs))
+    {
+    Some(ty::Instance { def: ty::InstanceDef::Item(did), .. }) => did,
+    _ => id,
+    }
+}
+
+/// Add notes explaining why `callee` isn't safe, following the recorded reasons down to the root cause
+pub fn explain_chain(db: &mut DiagnosticBuilder, tcx: TyCtxt, callee: DefId, tag: ::database::Tag, tag_name: &str)
+{
+    let lh = ::database::CACHE.read().unwrap();
+    let mut seen = Vec::new();
+    let mut cur = callee;
+    loop
+    {
+        seen.push(cur);
+        let name = tcx.def_path_str(cur);
+        let reason = match tcx.hir().as_local_hir_id(cur)
+            {
+            Some(node_id) => lh.get_local_reason(node_id, tag),
+            None => {
+                // A `#[not_safe]` attribute in the extern crate, or the tag list entry
+                let attrs = tcx.get_attrs(cur);
+                let attr_span = get_tag_spans(&attrs, AttrName::new("not_safe", "not"))
+                    .find(|&(name, _)| lh.get_tag_opt(&name.as_str()) == Some(tag))
+                    .map(|(_, span)| span);
+                match attr_span
+                {
+                Some(span) => Some(Reason::Attribute(span)),
+                None => lh.get_extern_reason(&tcx, cur.krate, cur.index, tag),
+                }
+                },
+            };
+        let next = match reason
+            {
+            Some(Reason::Call(span, next)) => {
+                db.span_note(span, &format!("`{}` calls `{}`", name, tcx.def_path_str(next)));
+                next
+                },
+            Some(Reason::Cycle(next)) => {
+                db.note(&format!("`{}` is recursive with `{}`", name, tcx.def_path_str(next)));
+                next
+                },
+            Some(Reason::Operation(span, what)) => {
+                db.span_note(span, &format!("`{}` is not {}-safe because of this {}", name, tag_name, what));
+                break;
+                },
+            Some(Reason::Attribute(span)) => {
+                db.span_note(span, &format!("`{}` is marked #[not_safe({})] here", name, tag_name));
+                break;
+                },
+            Some(Reason::ListEntry(file, line)) => {
+                db.note(&format!("`{}` is listed as not {}-safe at {}:{}", name, tag_name, file, line));
+                break;
+                },
+            Some(Reason::ListDefault(file)) => {
+                db.note(&format!("`{}` isn't listed in {}, which defaults to not {}-safe", name, file, tag_name));
+                break;
+                },
+            Some(Reason::UntaggedFfi) => {
+                db.note(&format!("`{}` is an untagged foreign function, which are assumed to not be {}-safe", name, tag_name));
+                break;
+                },
+            // (extern crates don't record a reason)
+            None if tcx.is_foreign_item(cur) && lh.tag_options(tag).ffi_unsafe => {
+                db.note(&format!("`{}` is an untagged foreign function, which are assumed to not be {}-safe", name, tag_name));
+                break;
+                },
+            Some(Reason::Checked(file)) => {
+                db.note(&format!("`{}` was found to not be {}-safe when its crate was checked (see {})", name, tag_name, file));
+                break;
+                },
+            None if !cur.is_local() && lh.tag_options(tag).extern_policy == ExternPolicy::AssumeUnsafe => {
+                db.note(&format!("`{}` has no tag information, and extern functions are assumed to not be {}-safe", name, tag_name));
+                break;
+                },
+            None => {
+                db.note(&format!("`{}` could not be shown to be {}-safe", name, tag_name));
+                break;
+                },
+            };
+        if seen.contains(&next) {
+            break;
+        }
+        cur = next;
+    }
+}
+
+/// Check if a span was generated by the expansion of `format_args!` (including `format_args_nl!`)
+pub fn is_format_args(span: &Span) -> bool
+{
+    match span.ctxt().outer_expn_data().kind
+    {
+    ExpnKind::Macro(_, name) => name.as_str().starts_with("format_args"),
+    _ => false,
+    }
+}
+
+/// Check if an expression is the function called by a call expression
+fn is_callee(tcx: TyCtxt, ex: &hir::Expr) -> bool
+{
+    match tcx.hir().get(tcx.hir().get_parent_node(ex.hir_id))
+    {
+    hir::Node::Expr(&hir::Expr { kind: ExprKind::Call(ref fcn, _), .. }) => fcn.hir_id == ex.hir_id,
+    _ => false,
+    }
+}
+
+/// Check if an expression is an argument of a function or method call
+fn is_call_argument(tcx: TyCtxt, ex: &hir::Expr) -> bool
+{
+    match tcx.hir().get(tcx.hir().get_parent_node(ex.hir_id))
+    {
+    hir::Node::Expr(parent) =>
+        match parent.kind
+        {
+        ExprKind::Call(ref fcn, _) => fcn.hir_id != ex.hir_id,
+        ExprKind::MethodCall(..) => true,
+        _ => false,
+        },
+    _ => false,
+    }
+}
+
+/// Look through the forwarding `Fn*` impls for references and boxes, to the type actually being called
+fn callable_self_ty<'tcx>(mut ty: Ty<'tcx>) -> Ty<'tcx>
+{
+    loop
+    {
+        ty = match ty.kind
+            {
+            ty::Ref(_, inner, _) => inner,
+            _ if ty.is_box() => ty.boxed_ty(),
+            _ => return ty,
+            };
+    }
+}
+
+/// Check if a call is through one of the `Fn*` traits on a generic parameter (e.g. `f()` for `f: F` with `F: Fn()`)
+///
+/// The closure or function passed in is checked where it's created (or passed), so the call itself is skipped.
+pub fn is_generic_callable_call<'tcx>(tcx: TyCtxt<'tcx>, did: DefId, substs: SubstsRef<'tcx>) -> bool
+{
+    let is_fn_trait = match tcx.trait_of_item(did)
+        {
+        Some(trait_did) => tcx.lang_items().fn_trait_kind(trait_did).is_some(),
+        None => false,
+        };
+    if !is_fn_trait {
+        return false;
+    }
+    match callable_self_ty(substs.type_at(0)).kind
+    {
+    ty::Param(_) => true,
+    _ => false,
+    }
+}
+
+/// Get the attributes on the slot (field, static or parameter) that an expression reads from
+fn slot_attrs<'tcx>(tcx: TyCtxt<'tcx>, tables: &ty::TypeckTables<'tcx>, ex: &hir::Expr) -> Option<ty::Attributes<'tcx>>
+{
+    match ex.kind
+    {
+    ExprKind::Field(ref base, _) => {
+        let mut ty = tables.expr_ty_adjusted(base);
+        while let ty::Ref(_, inner, _) = ty.kind {
+            ty = inner;
+        }
+        match ty.kind
+        {
+        ty::Adt(adt, _) if adt.is_struct() => {
+            let field_def = &adt.non_enum_variant().fields[tcx.field_index(ex.hir_id, tables)];
+            Some(tcx.get_attrs(field_def.did))
+            },
+        _ => None,
+        }
+        },
+    // An element of a tagged array (e.g. a dispatch table)
+    ExprKind::Index(ref base, _) => slot_attrs(tcx, tables, base),
+    ExprKind::Path(ref qp) =>
+        match tables.qpath_res(qp, ex.hir_id)
+        {
+        def::Res::Def(def::DefKind::Static, did) => Some(tcx.get_attrs(did)),
+        def::Res::Local(id) =>
+            match tcx.hir().get(tcx.hir().get_parent_node(id))
+            {
+            hir::Node::Param(p) => Some(ty::Attributes::Borrowed(&p.attrs)),
+            _ => None,
+            },
+        _ => None,
+        },
+    _ => None,
+    }
+}
+
+/// Get the attributes on the `extern { }` block containing a foreign function
+fn foreign_block_attrs<'tcx>(tcx: TyCtxt<'tcx>, node_id: hir::HirId) -> Option<&'tcx [ast::Attribute]>
+{
+    match tcx.hir().get(node_id)
+    {
+    hir::Node::ForeignItem(_) => Some(tcx.hir().attrs(tcx.hir().get_parent_item(node_id))),
+    _ => None,
+    }
+}
+
+/// Get the attributes on a parameter of a local function
+fn param_attrs<'tcx>(tcx: TyCtxt<'tcx>, did: DefId, idx: usize) -> Option<&'tcx [ast::Attribute]>
+{
+    let body = tcx.hir().maybe_body_owned_by(tcx.hir().as_local_hir_id(did)?)?;
+    tcx.hir().body(body).params.get(idx).map(|p| &p.attrs[..])
+}
+
+fn get_tags<'a>(meta_items: &'a [ast::Attribute], attr_name: AttrName) -> impl Iterator<Item=::syntax::symbol::Symbol>+'a {
+    get_tag_spans(meta_items, attr_name).map(|(name, _)| name)
+}
+
+/// Get the tags listed in an attribute, along with the span of each tag
+fn get_tag_spans<'a>(meta_items: &'a [ast::Attribute], attr_name: AttrName) -> impl Iterator<Item=(Symbol, Span)>+'a {
     meta_items.iter()
-        .filter(move |attr| attr.path == attr_name)
+        .filter(move |attr| attr_name.matches(&attr.path))
         .flat_map(|attr|
 			if let Some(v) = attr.meta() {
 				if let MetaItemKind::List(v) = v.kind {
@@ -304,7 +1453,7 @@ fn get_tags<'a>(meta_items: &'a [ast::Attribute], attr_name: Symbol) -> impl Ite
             if let NestedMetaItem::MetaItem(ref ptr) = tag_meta {
 				match (&ptr.kind, ptr.ident())
 				{
-				(&MetaItemKind::Word, Some(i)) => Some(i.name),
+				(&MetaItemKind::Word, Some(i)) => Some( (i.name, ptr.span) ),
 				_ => {
                     warn!("");
                     None
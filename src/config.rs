@@ -0,0 +1,259 @@
+//! Configuration
+//!
+//! Read from the `#![tag_safe_config(...)]` (or `#![tag_safe::config(...)]`) attribute on the crate root, and from a
+//! `tag_safe.toml` file found by searching upwards from the package directory. Options on the crate root take
+//! priority over those in the file. See the README for the available options.
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use syntax::ast;
+use rustc::session::Session;
+use check::AttrName;
+use database::{StaticCache,Backend,ExternPolicy,Severity};
+
+/// Name of the workspace configuration file
+const CONFIG_FILE: &str = "tag_safe.toml";
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ConfigFile
+{
+	backend: Option<String>,
+	#[serde(default)]
+	tags: BTreeMap<String, TagConfig>,
+	#[serde(default)]
+	crates: BTreeMap<String, CrateConfig>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct TagConfig
+{
+	description: Option<String>,
+	severity: Option<String>,
+	extern_policy: Option<String>,
+	ffi: Option<String>,
+	#[serde(default)]
+	strict_virtual: bool,
+	#[serde(default)]
+	fn_pointers: bool,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct CrateConfig
+{
+	#[serde(default)]
+	lists: Vec<String>,
+	#[serde(default)]
+	tags: BTreeMap<String, String>,
+}
+
+/// Find and load `tag_safe.toml`
+pub fn load_config_file(sess: &Session)
+{
+	let path = match find_config_file()
+		{
+		Some(v) => v,
+		None => return,
+		};
+	info!("Loading configuration from {}", path.display());
+	let config: ConfigFile = match ::std::fs::read_to_string(&path)
+		.map_err(|e| e.to_string())
+		.and_then(|s| ::toml::from_str(&s).map_err(|e| e.to_string()))
+		{
+		Ok(v) => v,
+		Err(e) => {
+			sess.err(&format!("Couldn't load {} - {}", path.display(), e));
+			return ;
+			},
+		};
+	::prescan::track_file(sess, &path);
+	let base = path.parent().expect("Config file has no parent directory");
+
+	let mut lh = ::database::CACHE.write().expect("Poisoned lock on tag_safe cache");
+	let error = |msg: String| sess.err(&format!("{}: {}", path.display(), msg));
+	if let Some(ref v) = config.backend {
+		match parse_backend(v)
+		{
+		Some(b) => lh.set_backend(b),
+		None => error(format!("Unknown backend `{}`, expected `hir` or `mir`", v)),
+		}
+	}
+	for (tag_name, tc) in &config.tags
+	{
+		let tag = lh.get_tag_or_add(tag_name);
+		let options = lh.tag_options_mut(tag);
+		options.description = tc.description.clone();
+		options.strict_virtual = tc.strict_virtual;
+		options.fn_pointers = tc.fn_pointers;
+		if let Some(ref v) = tc.severity {
+			match &v[..]
+			{
+			"allow" => options.severity = Severity::Allow,
+			"warn" => options.severity = Severity::Lint,
+			"deny" => options.severity = Severity::Deny,
+			_ => error(format!("Unknown severity `{}` for `{}`, expected `allow`, `warn` or `deny`", v, tag_name)),
+			}
+		}
+		if let Some(ref v) = tc.extern_policy {
+			match parse_extern_policy(v)
+			{
+			Some(p) => options.extern_policy = p,
+			None => error(format!("Unknown extern policy `{}` for `{}`, expected `assume_safe`, `assume_unsafe` or `warn`", v, tag_name)),
+			}
+		}
+		if let Some(ref v) = tc.ffi {
+			match parse_ffi(v)
+			{
+			Some(p) => options.ffi_unsafe = p,
+			None => error(format!("Unknown FFI default `{}` for `{}`, expected `safe` or `unsafe`", v, tag_name)),
+			}
+		}
+	}
+	for (crate_name, cc) in &config.crates
+	{
+		let lists = cc.lists.iter().map(|f| (None, f))
+			.chain( cc.tags.iter().map(|(t, f)| (Some(&t[..]), f)) );
+		for (tag_name, filename) in lists
+		{
+			let filename = base.join(filename);
+			match lh.load_crate(crate_name, tag_name, &filename.to_string_lossy(), None)
+			{
+			Ok(conflicts) => {
+				::prescan::track_file(sess, &filename);
+				for c in conflicts {
+					sess.warn(&c);
+				}
+				},
+			Err(e) => error(format!("Couldn't open tagging list file from '{}' - {}", filename.display(), e)),
+			}
+		}
+	}
+}
+
+/// Search for `tag_safe.toml` in the package directory and its parents
+fn find_config_file() -> Option<PathBuf>
+{
+	let start = match ::std::env::var_os("CARGO_MANIFEST_DIR")
+		{
+		Some(v) => PathBuf::from(v),
+		None => ::std::env::current_dir().ok()?,
+		};
+	let mut dir: Option<&Path> = Some(&start);
+	while let Some(d) = dir
+	{
+		let path = d.join(CONFIG_FILE);
+		if path.is_file() {
+			return Some(path);
+		}
+		dir = d.parent();
+	}
+	None
+}
+
+pub fn load_crate_config(sess: &Session, attrs: &[ast::Attribute], attr_name: AttrName)
+{
+	let mut lh = ::database::CACHE.write().expect("Poisoned lock on tag_safe cache");
+	for attr in attrs.iter().filter(|a| attr_name.matches(&a.path))
+	{
+		let items = match attr.meta_item_list()
+			{
+			Some(v) => v,
+			None => {
+				sess.span_err(attr.span, "#[tag_safe_config] must take a list");
+				continue ;
+				},
+			};
+		for item in items
+		{
+			match item.meta_item()
+			{
+			Some(mi) if mi.is_meta_item_list() => load_tag_options(sess, &mut lh, mi),
+			Some(mi) if mi.is_value_str() => load_global_option(sess, &mut lh, mi),
+			_ => sess.span_err(item.span(), "Expected `tag(options...)` or `option = \"value\"`"),
+			}
+		}
+	}
+}
+
+fn load_global_option(sess: &Session, lh: &mut StaticCache, meta_item: &ast::MetaItem)
+{
+	let value = meta_item.value_str().expect("Checked by caller");
+	match (&*meta_item.name_or_empty().as_str(), &*value.as_str())
+	{
+	("backend", v) =>
+		match parse_backend(v)
+		{
+		Some(b) => lh.set_backend(b),
+		None => sess.span_err(meta_item.span, &format!("Unknown backend `{}`, expected `hir` or `mir`", v)),
+		},
+	(name, _) => sess.span_err(meta_item.span, &format!("Unknown tag_safe option `{}`", name)),
+	}
+}
+
+fn load_tag_options(sess: &Session, lh: &mut StaticCache, meta_item: &ast::MetaItem)
+{
+	let tag = lh.get_tag_or_add(&meta_item.name_or_empty().as_str());
+	for opt in meta_item.meta_item_list().unwrap_or(&[])
+	{
+		let opt = match opt.meta_item()
+			{
+			Some(v) => v,
+			None => {
+				sess.span_err(opt.span(), "Expected a tag option");
+				continue ;
+				},
+			};
+		let options = lh.tag_options_mut(tag);
+		match (&*opt.name_or_empty().as_str(), opt.value_str())
+		{
+		("strict_virtual", None) => options.strict_virtual = true,
+		("fn_pointers", None) => options.fn_pointers = true,
+		("ffi", Some(v)) =>
+			match parse_ffi(&v.as_str())
+			{
+			Some(p) => options.ffi_unsafe = p,
+			None => sess.span_err(opt.span, &format!("Unknown FFI default `{}`, expected `safe` or `unsafe`", v)),
+			},
+		("extern_policy", Some(v)) =>
+			match parse_extern_policy(&v.as_str())
+			{
+			Some(p) => options.extern_policy = p,
+			None => sess.span_err(opt.span, &format!("Unknown extern policy `{}`, expected `assume_safe`, `assume_unsafe` or `warn`", v)),
+			},
+		(name, _) => sess.span_err(opt.span, &format!("Unknown tag_safe option `{}`", name)),
+		}
+	}
+}
+
+fn parse_backend(v: &str) -> Option<Backend>
+{
+	match v
+	{
+	"hir" => Some(Backend::Hir),
+	"mir" => Some(Backend::Mir),
+	_ => None,
+	}
+}
+
+fn parse_extern_policy(v: &str) -> Option<ExternPolicy>
+{
+	match v
+	{
+	"assume_safe" => Some(ExternPolicy::AssumeSafe),
+	"assume_unsafe" => Some(ExternPolicy::AssumeUnsafe),
+	"warn" => Some(ExternPolicy::Warn),
+	_ => None,
+	}
+}
+
+/// Parse the `ffi` option, returning true if untagged foreign functions are unsafe
+fn parse_ffi(v: &str) -> Option<bool>
+{
+	match v
+	{
+	"safe" => Some(false),
+	"unsafe" => Some(true),
+	_ => None,
+	}
+}
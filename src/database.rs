@@ -4,14 +4,27 @@ use std::collections::{HashMap,HashSet,hash_map};
 use rustc::hir;
 use rustc::hir::def_id;
 use rustc::ty::TyCtxt;
+use syntax::source_map::Span;
+use taglist::{TagList, Section, Entry};
+use sidecar::Sidecar;
+use paths::ReexportMap;
 
 #[derive(Default)]
 pub struct StaticCache
 {
 	known_tags: Vec<String>,
+	backend: Backend,
+	// tag -> options
+	tag_options: HashMap<usize, TagOptions>,
 	this_crate: AnnotationCache,
 	//ext_crates: HashMap<CrateNum, AnnotationCache>,
 	ext_crates: HashMap<String, ExtCache>,
+	// Results written when extern crates were checked (`None` if the crate wasn't checked)
+	sidecars: RwLock< HashMap<def_id::CrateNum, Option<Sidecar>> >,
+	// Public paths to the items of extern crates with tag lists
+	reexports: RwLock< HashMap<def_id::CrateNum, ReexportMap> >,
+	// (function, tag) -> status from tag lists or results files
+	extern_results: RwLock< HashMap<(def_id::DefId, usize), Option<bool>> >,
 }
 
 #[derive(Default)]
@@ -26,6 +39,8 @@ struct TagAnnotationCache
 {
 	// functon -> state
 	map: HashMap<hir::HirId, bool>,
+	// function -> why it isn't safe
+	reasons: HashMap<hir::HirId, Reason>,
 }
 
 #[derive(Default)]
@@ -34,21 +49,187 @@ struct ExtCache
 	// tag -> cache
 	tag_map: HashMap<usize, ExtTagCache>,
 }
-#[derive(Default)]
+/// The merged lists for a tag of an extern crate
 struct ExtTagCache
 {
-	default: bool,
-	// name -> inner ID mapping
-	name_set: HashSet<String>,
-	// def_id -> inner ID mapping
-	id_map: RwLock< HashMap<def_id::DefIndex,bool> >,
+	// Default set by a list, and the index of that file (functions are safe if no list sets it)
+	default: Option<(bool, usize)>,
+	// Files the lists were loaded from, in load order
+	files: Vec<String>,
+	// Span of the attribute that loaded each file (if it was loaded by an attribute)
+	file_spans: Vec<Option<Span>>,
+	// name -> (is safe, (file index, line number in the list))
+	names: HashMap<String, (bool, (usize, usize))>,
+	// Entries containing `*` with their file index, in load order
+	globs: Vec<(usize, Entry)>,
+}
+impl ExtTagCache
+{
+	fn new() -> Self {
+		ExtTagCache {
+			default: None,
+			files: Vec::new(),
+			file_spans: Vec::new(),
+			names: HashMap::new(),
+			globs: Vec::new(),
+			}
+	}
+	/// Merge in a list, with its entries overriding existing ones. Returns descriptions of any entries that conflict
+	/// with an earlier list.
+	fn merge(&mut self, filename: &str, span: Option<Span>, section: Section) -> Vec<String> {
+		let mut conflicts = Vec::new();
+		let file = self.files.len();
+		self.files.push(filename.to_string());
+		self.file_spans.push(span);
+		if let Some(default) = section.default {
+			if let Some((prev, prev_file)) = self.default {
+				if prev != default {
+					conflicts.push(format!("the default is {} in {}, but {} in {}",
+						safe_str(prev), self.files[prev_file], safe_str(default), filename));
+				}
+			}
+			self.default = Some( (default, file) );
+		}
+		for mut e in section.entries
+		{
+			e.pattern = ::paths::normalise(&e.pattern);
+			let prev = if e.is_glob() {
+					self.globs.iter().rev().find(|g| g.1.pattern == e.pattern).map(|g| (g.1.is_safe, (g.0, g.1.line)))
+				}
+				else {
+					self.names.get(&e.pattern).cloned()
+				};
+			if let Some((is_safe, (prev_file, prev_line))) = prev {
+				if is_safe != e.is_safe && prev_file != file {
+					conflicts.push(format!("`{}` is {} at {}:{}, but {} at {}:{}", e.pattern,
+						safe_str(is_safe), self.files[prev_file], prev_line, safe_str(e.is_safe), filename, e.line));
+				}
+			}
+			if e.is_glob() {
+				self.globs.push( (file, e) );
+			}
+			else {
+				self.names.insert(e.pattern.clone(), (e.is_safe, (file, e.line)));
+			}
+		}
+		conflicts
+	}
+	/// Get the status of a function from any of its names, and the file and line of the entry that set it (the last
+	/// matching entry wins)
+	fn lookup(&self, names: &[String]) -> (bool, Option<(&str, usize)>) {
+		let exact = names.iter().filter_map(|n| self.names.get(n).cloned());
+		let glob = self.globs.iter().rev().find(|e| names.iter().any(|n| e.1.matches(n))).map(|e| (e.1.is_safe, (e.0, e.1.line)));
+		match exact.chain(glob).max_by_key(|&(_, pos)| pos)
+		{
+		Some((is_safe, (file, line))) => (is_safe, Some((&self.files[file][..], line))),
+		None => (self.default.map_or(true, |(v, _)| v), None),
+		}
+	}
+	/// Get the file that set the default
+	fn default_file(&self) -> &str {
+		&self.files[self.default.map_or(0, |(_, file)| file)]
+	}
+}
+
+/// Find an extern crate by name
+fn find_crate(tcx: &TyCtxt, crate_name: &str) -> Option<def_id::CrateNum> {
+	tcx.crates().iter().cloned().find(|&k| *tcx.crate_name(k).as_str() == *crate_name)
+}
 
-	//map: Vec<bool>,
+fn safe_str(is_safe: bool) -> &'static str {
+	if is_safe { "safe" } else { "unsafe" }
 }
 
-#[derive(Copy,Clone)]
+#[derive(Copy,Clone,PartialEq,Eq,Hash,Debug)]
 pub struct Tag(usize);
 
+/// Why a function isn't safe for a tag (used to explain the chain of calls in diagnostics)
+#[derive(Clone,Debug)]
+pub enum Reason
+{
+	/// Marked with `#[not_safe(tag)]` (the span of the tag within the attribute)
+	Attribute(Span),
+	/// Calls a function that isn't safe
+	Call(Span, def_id::DefId),
+	/// Contains another operation that isn't safe (e.g. a call through a function pointer)
+	Operation(Span, String),
+	/// Part of a recursive cycle, rooted at the given function, that isn't safe
+	Cycle(def_id::DefId),
+	/// Listed in a tag list file (file name and line number)
+	ListEntry(String, usize),
+	/// Not listed in a tag list file that defaults to unsafe
+	ListDefault(String),
+	/// Found to be unsafe when the extern crate was checked (results file name)
+	Checked(String),
+	/// An untagged foreign function, with `ffi = "unsafe"`
+	UntaggedFfi,
+}
+
+/// Method used to find the functions called by a function
+#[derive(Copy,Clone,PartialEq,Debug)]
+pub enum Backend
+{
+	/// Walk the HIR of function bodies
+	Hir,
+	/// Use the `Call` and `Drop` terminators of each instance's MIR
+	Mir,
+}
+impl Default for Backend {
+	fn default() -> Self {
+		Backend::Hir
+	}
+}
+
+/// Per-tag options, set using `#![tag_safe_config(tag(...))]` or `tag_safe.toml`
+#[derive(Default,Clone)]
+pub struct TagOptions
+{
+	/// Calls through a trait object must be to a tagged trait method
+	pub strict_virtual: bool,
+	/// Calls through function pointers must use a tagged slot, and storing into tagged slots is checked
+	pub fn_pointers: bool,
+	/// How extern functions without any tag information are treated
+	pub extern_policy: ExternPolicy,
+	/// Untagged foreign functions (declared in `extern { }` blocks) are unsafe
+	pub ffi_unsafe: bool,
+	/// How violations are reported
+	pub severity: Severity,
+	/// Description of the tag (from `tag_safe.toml`), included in diagnostics
+	pub description: Option<String>,
+}
+
+/// How `not_tagged_safe` violations for a tag are reported
+#[derive(Copy,Clone,PartialEq,Debug)]
+pub enum Severity
+{
+	/// Not checked
+	Allow,
+	/// Reported using the `not_tagged_safe` lint (so the level can be changed with `#[deny]`/`#[allow]`)
+	Lint,
+	/// Always an error
+	Deny,
+}
+impl Default for Severity {
+	fn default() -> Self {
+		Severity::Lint
+	}
+}
+
+/// Treatment of extern functions that aren't tagged, listed, or in the results from checking their crate
+#[derive(Copy,Clone,PartialEq,Debug)]
+pub enum ExternPolicy
+{
+	AssumeSafe,
+	AssumeUnsafe,
+	/// Assume safe, but emit `tag_safe_assumed_extern`
+	Warn,
+}
+impl Default for ExternPolicy {
+	fn default() -> Self {
+		ExternPolicy::AssumeSafe
+	}
+}
+
 lazy_static! {
 	// RwLock becuase after building, this will be uncontended.
 	pub static ref CACHE: RwLock<StaticCache> = Default::default();
@@ -66,11 +247,33 @@ impl StaticCache
 			Tag(i)
 		}
 	}
-	//pub fn get_tag_opt(&self, tag_name: &str) -> Option<Tag> {
-	//	self.known_tags.iter()
-	//		.position(|x| x == tag_name)
-	//		.map(|i| Tag(i))
-	//}
+	pub fn known_tags(&self) -> Vec<(Tag, String)> {
+		self.known_tags.iter().enumerate()
+			.map(|(i, name)| (Tag(i), name.clone()))
+			.collect()
+	}
+	pub fn tag_name(&self, tag: Tag) -> &str {
+		&self.known_tags[tag.0]
+	}
+	pub fn get_tag_opt(&self, tag_name: &str) -> Option<Tag> {
+		self.known_tags.iter()
+			.position(|x| x == tag_name)
+			.map(|i| Tag(i))
+	}
+
+	pub fn backend(&self) -> Backend {
+		self.backend
+	}
+	pub fn set_backend(&mut self, backend: Backend) {
+		self.backend = backend;
+	}
+
+	pub fn tag_options(&self, tag: Tag) -> TagOptions {
+		self.tag_options.get(&tag.0).cloned().unwrap_or_default()
+	}
+	pub fn tag_options_mut(&mut self, tag: Tag) -> &mut TagOptions {
+		self.tag_options.entry(tag.0).or_insert_with(|| Default::default())
+	}
 
 	pub fn mark(&mut self, id: hir::HirId, tag: Tag, is_safe: bool) {
 		let tag_cache = self.this_crate.map.entry(tag.0).or_insert_with(|| Default::default());
@@ -80,38 +283,62 @@ impl StaticCache
 		hash_map::Entry::Vacant(e) => { e.insert(is_safe); },
 		}
 	}
-	pub fn load_crate(&mut self, crate_name: &str, tag: Tag, filename: &str) -> Result<(),::std::io::Error> {
-		use ::std::io::BufRead;
-		let mut fp = match ::std::fs::File::open(filename)
+	/// Record why a function isn't safe (the first reason found is kept)
+	pub fn set_reason(&mut self, id: hir::HirId, tag: Tag, reason: Reason) {
+		let tag_cache = self.this_crate.map.entry(tag.0).or_insert_with(|| Default::default());
+		tag_cache.reasons.entry(id).or_insert(reason);
+	}
+	/// Load a tag list for an extern crate, either the section for `tag_name` or (for version 2 lists) every section
+	///
+	/// Returns descriptions of the entries that conflict with a list loaded earlier (which are overridden). `span` is
+	/// the attribute that loaded the list, if any.
+	pub fn load_crate(&mut self, crate_name: &str, tag_name: Option<&str>, filename: &str, span: Option<Span>) -> Result<Vec<String>,::std::io::Error> {
+		let list = match TagList::load(filename)
 			{
-			Ok(v) => ::std::io::BufReader::new(v),
+			Ok(v) => v,
 			Err(e) => {
-				error!("Cannot open file '{}'", filename);
+				error!("Cannot load file '{}'", filename);
 				return Err(e);
 				},
 			};
-		// Line 1: Default
-		let default = {
-			let mut line = String::new();
-			fp.read_line(&mut line)?;
-			match line.trim()
-			{
-			"true" => true,
-			"false" => false,
-			_ => return Err(::std::io::Error::new(::std::io::ErrorKind::InvalidData, "First line wasn't `true` or `false`")),
-			}
-			};
-		let cache = match self.ext_crates.entry(String::from(crate_name)).or_insert_with(|| Default::default()).tag_map.entry(tag.0)
+		let sections: Vec<_> = match tag_name
 			{
-			hash_map::Entry::Occupied(_) => return Ok( () ),
-			hash_map::Entry::Vacant(e) => e.insert(ExtTagCache { default: default, ..Default::default() }),
+			Some(name) => {
+				let sections: Vec<_> = list.sections.into_iter()
+					.filter(|s| s.tag.as_ref().map(|t| t == name).unwrap_or(true))
+					.map(|s| (name.to_string(), s))
+					.collect();
+				if sections.is_empty() {
+					return Err(::std::io::Error::new(::std::io::ErrorKind::InvalidData, format!("No `[{}]` section", name)));
+				}
+				sections
+				},
+			None => {
+				if list.sections.iter().any(|s| s.tag.is_none()) {
+					return Err(::std::io::Error::new(::std::io::ErrorKind::InvalidData, "Version 1 lists only hold one tag, use `tag=\"file\"`"));
+				}
+				list.sections.into_iter().map(|s| (s.tag.clone().unwrap(), s)).collect()
+				},
 			};
-		// Rest: Entries
-		for line in fp.lines()
+		let mut conflicts = Vec::new();
+		for (name, mut section) in sections
 		{
-			cache.name_set.insert( line? );
+			section.tag = Some(name);
+			conflicts.extend( self.add_section(crate_name, filename, span, section) );
 		}
-		Ok( () )
+		Ok(conflicts)
+	}
+	/// Add the entries for a tag, merged with (and overriding) any loaded earlier. Returns descriptions of the
+	/// entries that conflict with an earlier list.
+	pub fn add_section(&mut self, crate_name: &str, filename: &str, span: Option<Span>, section: Section) -> Vec<String> {
+		let tag_name = section.tag.clone().expect("Adding a tag list section without a tag");
+		let tag = self.get_tag_or_add(&tag_name);
+		self.ext_crates.entry(String::from(crate_name)).or_insert_with(|| Default::default())
+			.tag_map.entry(tag.0).or_insert_with(ExtTagCache::new)
+			.merge(filename, span, section)
+			.into_iter()
+			.map(|c| format!("Conflicting `{}` entries for crate `{}`: {}", tag_name, crate_name, c))
+			.collect()
 	}
 
 	pub fn get_local(&self, id: hir::HirId, tag: Tag) -> Option<bool> {
@@ -119,31 +346,163 @@ impl StaticCache
 			.and_then(|tc| tc.map.get(&id))
 			.map(|&v| v)
 	}
+	pub fn get_local_reason(&self, id: hir::HirId, tag: Tag) -> Option<Reason> {
+		self.this_crate.map.get(&tag.0)
+			.and_then(|tc| tc.reasons.get(&id))
+			.cloned()
+	}
 	pub fn get_extern(&self, tcx: &TyCtxt, krate: def_id::CrateNum, index: def_id::DefIndex, tag: Tag) -> Option<bool> {
-		let cache = match self.ext_crates.get(&*tcx.crate_name(krate).as_str()).and_then(|c| c.tag_map.get(&tag.0))
+		let did = def_id::DefId { krate: krate, index: index };
+		if let Some(&v) = self.extern_results.read().unwrap().get(&(did, tag.0)) {
+			return v;
+		}
+		let rv = match self.lookup_extern(tcx, did, tag)
 			{
-			None => return None,
-			Some(e) => e,
+			Some(v) => Some(v.is_ok()),
+			// No list, use the results from when the crate was checked
+			None => self.get_checked(tcx, krate, index, tag).map(|(v, _)| v),
 			};
-		if let Some(v) = cache.id_map.read().unwrap().get(&index)
+		self.extern_results.write().unwrap().insert( (did, tag.0), rv );
+		rv
+	}
+
+	/// Get the list entry that makes an extern function unsafe
+	pub fn get_extern_reason(&self, tcx: &TyCtxt, krate: def_id::CrateNum, index: def_id::DefIndex, tag: Tag) -> Option<Reason> {
+		match self.lookup_extern(tcx, def_id::DefId { krate: krate, index: index }, tag)
 		{
-			return Some(*v);
+		Some(Ok( () )) => None,
+		Some(Err(reason)) => Some(reason),
+		None => match self.get_checked(tcx, krate, index, tag)
+			{
+			Some((false, filename)) => Some(Reason::Checked(filename)),
+			_ => None,
+			},
 		}
-		
-		match cache.id_map.write().unwrap().entry(index)
+	}
+
+	/// Look up an extern function in the tag lists that apply to it (`None` if there aren't any)
+	///
+	/// These are the lists for its crate, and for crates that re-export it (e.g. a list for `std` applies to the
+	/// items of `core` and `alloc` that `std` re-exports). An entry in any of the lists is used before a default,
+	/// checking the function's own crate first.
+	fn lookup_extern(&self, tcx: &TyCtxt, did: def_id::DefId, tag: Tag) -> Option<Result<(), Reason>> {
+		let mut lists = Vec::new();
+		for (crate_name, ec) in &self.ext_crates
 		{
-		hash_map::Entry::Occupied(e) => Some(*e.get()),
-		hash_map::Entry::Vacant(e) => {
-			let name = tcx.def_path_str(def_id::DefId{krate:krate,index:index});
-			debug!("Look up {}", name);
-			if cache.name_set.contains( &name ) {
-				Some(*e.insert( !cache.default ))
+			let cache = match ec.tag_map.get(&tag.0)
+				{
+				Some(v) => v,
+				None => continue,
+				};
+			let krate = if *tcx.crate_name(did.krate).as_str() == crate_name[..] {
+					did.krate
+				}
+				else {
+					match find_crate(tcx, crate_name)
+					{
+					Some(v) => v,
+					None => continue,
+					}
+				};
+			self.load_reexports(tcx, krate);
+			let reexports = self.reexports.read().unwrap();
+			if krate == did.krate || ::paths::reexported_by(*tcx, &reexports[&krate], did) {
+				lists.push( (krate != did.krate, crate_name, cache, ::paths::item_names(*tcx, &reexports[&krate], did)) );
 			}
-			else {
-				Some(*e.insert( cache.default ))
+		}
+		lists.sort_by(|a, b| (a.0, a.1).cmp(&(b.0, b.1)));
+
+		let mut default = None;
+		for &(_, _, cache, ref names) in &lists
+		{
+			debug!("Look up {:?}", names);
+			match cache.lookup(names)
+			{
+			(true, Some(_)) => return Some(Ok( () )),
+			(false, Some((file, line))) => return Some(Err(Reason::ListEntry(file.to_string(), line))),
+			(is_safe, None) => if default.is_none() {
+				default = Some(if is_safe { Ok( () ) } else { Err(Reason::ListDefault(cache.default_file().to_string())) });
+				},
 			}
-			},
+		}
+		default
+	}
+	fn load_reexports(&self, tcx: &TyCtxt, krate: def_id::CrateNum) {
+		if !self.reexports.read().unwrap().contains_key(&krate) {
+			let map = ::paths::reexport_map(*tcx, krate);
+			self.reexports.write().unwrap().insert(krate, map);
 		}
 	}
-}
 
+	/// Find list entries that don't match any exported function of their crate (named the same way as when looking up
+	/// a function), and lists for crates that aren't dependencies. Returns the span of the attribute that loaded the
+	/// list (if any) and a description of the problem.
+	pub fn stale_entries(&self, tcx: &TyCtxt) -> Vec<(Option<Span>, String)> {
+		let mut rv = Vec::new();
+		for (crate_name, ec) in &self.ext_crates
+		{
+			let krate = match find_crate(tcx, crate_name)
+				{
+				Some(k) => k,
+				None => {
+					// Reported against the first attribute that loaded a list for it (if any)
+					let span = ec.tag_map.values().flat_map(|tc| tc.file_spans.iter().filter_map(|&s| s)).min();
+					rv.push( (span, format!("Tag lists are loaded for `{}`, which isn't a dependency of this crate", crate_name)) );
+					continue ;
+					},
+				};
+			self.load_reexports(tcx, krate);
+			let names: HashSet<String> = {
+				let reexports = self.reexports.read().unwrap();
+				::paths::exported_fns(*tcx, krate, &reexports[&krate]).into_iter()
+					.flat_map(|did| ::paths::item_names(*tcx, &reexports[&krate], did))
+					.collect()
+				};
+			debug!("{} names for exported functions of {}", names.len(), crate_name);
+			for tc in ec.tag_map.values()
+			{
+				let exact = tc.names.iter()
+					.filter(|&(pattern, _)| !names.contains(pattern))
+					.map(|(pattern, &(_, (file, line)))| (file, line, pattern));
+				let globs = tc.globs.iter()
+					.filter(|&&(_, ref e)| !names.iter().any(|n| e.matches(n)))
+					.map(|&(file, ref e)| (file, e.line, &e.pattern));
+				let mut stale: Vec<_> = exact.chain(globs).collect();
+				stale.sort();
+				for (file, line, pattern) in stale
+				{
+					rv.push( (tc.file_spans[file], format!("`{}` at {}:{} doesn't match any exported function of `{}`", pattern, tc.files[file], line, crate_name)) );
+				}
+			}
+		}
+		rv
+	}
+
+	/// Get the result from when an extern crate was checked (along with the results file name)
+	fn get_checked(&self, tcx: &TyCtxt, krate: def_id::CrateNum, index: def_id::DefIndex, tag: Tag) -> Option<(bool, String)> {
+		let did = def_id::DefId { krate: krate, index: index };
+		let tag_name = &self.known_tags[tag.0];
+		self.load_sidecar(tcx, krate);
+		let sidecars = self.sidecars.read().unwrap();
+		let sidecar = sidecars.get(&krate).and_then(|v| v.as_ref())?;
+		sidecar.get(*tcx, tag_name, did).map(|v| (v, sidecar.filename.clone()))
+	}
+	/// Get the names of the tags in the results of every checked dependency
+	pub fn dependency_tags(&self, tcx: &TyCtxt) -> Vec<String> {
+		let mut rv = Vec::new();
+		for &krate in tcx.crates().iter()
+		{
+			self.load_sidecar(tcx, krate);
+			if let Some(sidecar) = self.sidecars.read().unwrap().get(&krate).and_then(|v| v.as_ref()) {
+				rv.extend( sidecar.tag_names().map(|t| t.to_string()) );
+			}
+		}
+		rv
+	}
+	fn load_sidecar(&self, tcx: &TyCtxt, krate: def_id::CrateNum) {
+		if !self.sidecars.read().unwrap().contains_key(&krate) {
+			let sidecar = Sidecar::load_for(*tcx, krate);
+			self.sidecars.write().unwrap().insert(krate, sidecar);
+		}
+	}
+}
@@ -0,0 +1,190 @@
+//! Export of the tags of this crate's public functions, for use by dependent crates
+//!
+//! For library crates, the results are always written next to the crate's output (see `sidecar`), where they're found automatically when
+//! checking dependent crates. If `TAG_SAFE_EXPORT` is set to a directory (e.g. using `cargo tag-safe --export <dir>`),
+//! they're also written as a tag list to `<dir>/<crate name>.tags`, naming each function by its public path (e.g. the
+//! path it's re-exported as, which is how dependent crates see it). With `TAG_SAFE_EXPORT_IDS` set (`--export-ids`),
+//! the list names functions as `hash:<DefPathHash>` (with the path in a comment), which doesn't depend on how rustc
+//! prints paths.
+use std::path::Path;
+use rustc::hir;
+use rustc::hir::def::{DefKind, Res};
+use rustc::hir::def_id::{DefId, LOCAL_CRATE};
+use rustc::lint::LateContext;
+use rustc::ty::{self, TyCtxt};
+use check::Pass;
+use paths::ReexportMap;
+use taglist::{TagList, Section, Entry};
+
+/// Environment variable holding the directory to write tag lists to
+pub const EXPORT_VAR: &str = "TAG_SAFE_EXPORT";
+/// If set, exported entries name functions by `DefPathHash` instead of by path
+pub const EXPORT_IDS_VAR: &str = "TAG_SAFE_EXPORT_IDS";
+
+/// Add the tags that dependencies have results for, and those named by the attributes of the extern functions that
+/// this crate uses. These can make this crate's functions unsafe without the crate naming the tag itself.
+pub fn add_dependency_tags<'tcx>(pass: &Pass, cx: &LateContext<'_, 'tcx>, krate: &hir::Crate)
+{
+	let tcx = cx.tcx;
+	let mut names = ::database::CACHE.read().unwrap().dependency_tags(&tcx);
+	for &body_id in krate.body_ids.iter()
+	{
+		let mut v = ExternCallees { tables: tcx.body_tables(body_id), callees: Vec::new() };
+		hir::intravisit::Visitor::visit_body(&mut v, tcx.hir().body(body_id));
+		for did in v.callees
+		{
+			names.extend( pass.attr_tag_names(&tcx.get_attrs(did)).into_iter().map(|name| name.to_string()) );
+		}
+	}
+	let mut lh = ::database::CACHE.write().unwrap();
+	for name in names
+	{
+		lh.get_tag_or_add(&name);
+	}
+}
+
+/// Collects the extern functions used by a body
+struct ExternCallees<'tcx>
+{
+	tables: &'tcx ty::TypeckTables<'tcx>,
+	callees: Vec<DefId>,
+}
+impl<'tcx> hir::intravisit::Visitor<'tcx> for ExternCallees<'tcx>
+{
+	fn nested_visit_map<'this>(&'this mut self) -> hir::intravisit::NestedVisitorMap<'this, 'tcx> {
+		// Closure bodies are also in the crate's list of bodies
+		hir::intravisit::NestedVisitorMap::None
+	}
+
+	fn visit_expr(&mut self, ex: &'tcx hir::Expr) {
+		let did = match ex.kind
+			{
+			hir::ExprKind::Path(ref qp) =>
+				match self.tables.qpath_res(qp, ex.hir_id)
+				{
+				Res::Def(DefKind::Fn, did) | Res::Def(DefKind::Method, did) => Some(did),
+				_ => None,
+				},
+			hir::ExprKind::MethodCall(..) => self.tables.type_dependent_def_id(ex.hir_id),
+			_ => None,
+			};
+		if let Some(did) = did {
+			if !did.is_local() {
+				self.callees.push(did);
+			}
+		}
+		hir::intravisit::walk_expr(self, ex);
+	}
+}
+
+/// Write the status of every public function, for each known tag, next to the crate's output
+pub fn write_results(pass: &mut Pass, cx: &LateContext, krate: &hir::Crate)
+{
+	let fns = public_fns(cx, krate);
+	let tags = ::database::CACHE.read().unwrap().known_tags();
+	let results: Vec<(String, Vec<(DefId, bool)>)> = tags.into_iter()
+		.map(|(tag, tag_name)| (tag_name, fns.iter().map(|&did| (did, pass.method_is_safe(cx, did, tag))).collect()))
+		.collect();
+	match ::sidecar::write(cx.tcx, &results)
+	{
+	Ok(path) => info!("Wrote results for {} functions to {}", fns.len(), path.display()),
+	Err(e) => cx.tcx.sess.warn(&format!("Couldn't write tag_safe results for dependent crates - {}", e)),
+	}
+}
+
+/// Write the status of every public function, for each known tag, to `<dir>/<crate name>.tags`
+pub fn export_tags(pass: &mut Pass, cx: &LateContext, krate: &hir::Crate, dir: &str)
+{
+	let tcx = cx.tcx;
+	let crate_name = tcx.crate_name(LOCAL_CRATE).as_str();
+
+	let reexports = ::paths::reexport_map(tcx, LOCAL_CRATE);
+	let mut fns: Vec<(DefId, String)> = public_fns(cx, krate).into_iter()
+		.map(|did| (did, export_path(tcx, &crate_name, &reexports, did)))
+		.collect();
+	fns.sort_by(|a, b| a.1.cmp(&b.1));
+
+	let use_ids = ::std::env::var_os(EXPORT_IDS_VAR).is_some();
+	let tags = ::database::CACHE.read().unwrap().known_tags();
+	let mut list = TagList { sections: Vec::new() };
+	for (tag, tag_name) in tags
+	{
+		let entries = fns.iter()
+			.map(|&(did, ref path)| {
+				let is_safe = pass.method_is_safe(cx, did, tag);
+				if use_ids {
+					Entry { pattern: ::paths::hash_name(tcx, did), is_safe: is_safe, line: 0, comment: Some(path.clone()) }
+				}
+				else {
+					Entry { pattern: path.clone(), is_safe: is_safe, line: 0, comment: None }
+				}
+				})
+			.collect();
+		list.sections.push(Section { tag: Some(tag_name), default: Some(true), entries: entries });
+	}
+
+	let filename = Path::new(dir).join(format!("{}.tags", crate_name));
+	info!("Exporting tags for {} functions to {}", fns.len(), filename.display());
+	let rv = ::std::fs::create_dir_all(dir)
+		.and_then(|_| ::std::fs::File::create(&filename))
+		.and_then(|mut fp| list.write(&mut fp, &format!("Exported from crate `{}`", crate_name)));
+	if let Err(e) = rv {
+		tcx.sess.err(&format!("Couldn't write tag list to '{}' - {}", filename.display(), e));
+	}
+}
+
+/// Get the functions (with bodies, or declared in `extern { }` blocks) that can be called from other crates
+fn public_fns(cx: &LateContext, krate: &hir::Crate) -> Vec<DefId>
+{
+	let mut rv = Vec::new();
+	{
+		let mut add = |id: hir::HirId| if cx.access_levels.is_exported(id) {
+			rv.push( cx.tcx.hir().local_def_id(id) );
+			};
+		for item in krate.items.values()
+		{
+			match item.kind
+			{
+			hir::ItemKind::Fn(..) => add(item.hir_id),
+			hir::ItemKind::ForeignMod(ref fm) => for fi in fm.items.iter() {
+				if let hir::ForeignItemKind::Fn(..) = fi.kind {
+					add(fi.hir_id);
+				}
+				},
+			_ => {},
+			}
+		}
+		for item in krate.impl_items.values()
+		{
+			if let hir::ImplItemKind::Method(..) = item.kind {
+				add(item.hir_id);
+			}
+		}
+		for item in krate.trait_items.values()
+		{
+			if let hir::TraitItemKind::Method(_, hir::TraitMethod::Provided(_)) = item.kind {
+				add(item.hir_id);
+			}
+		}
+	}
+	rv
+}
+
+/// Get the path to a local item as named from another crate (i.e. starting with this crate's name), using the
+/// public path if there is one
+fn export_path(tcx: TyCtxt, crate_name: &str, reexports: &ReexportMap, did: DefId) -> String
+{
+	let path = ty::print::with_crate_prefix(|| ::paths::public_path(tcx, reexports, did).unwrap_or_else(|| tcx.def_path_str(did)));
+	// Replace `crate` at the start of each path (there can be several, e.g. `<crate::Foo as crate::Bar>::baz`)
+	let mut rv = String::new();
+	let mut rest = &path[..];
+	while let Some(pos) = rest.find("crate::")
+	{
+		let is_segment_start = rest[..pos].chars().next_back().map(|c| !(c.is_alphanumeric() || c == '_')).unwrap_or(true);
+		rv.push_str(&rest[..pos]);
+		rv.push_str(if is_segment_start { crate_name } else { "crate" });
+		rest = &rest[pos + "crate".len() ..];
+	}
+	rv.push_str(rest);
+	rv
+}
@@ -1,26 +1,27 @@
 // tag_safe
 //
-// A linting plugin to flag calls to methods not marked "tag_safe"
-// from methods marked "tag_safe".
+// A lint to flag calls to functions that aren't safe for a tag from functions that require it,
+// run using the `tag-safe-driver` wrapper around rustc.
 //
 // Author: John Hodge (thePowersGang/Mutabah)
 //
-// TODO: Support '#[tag_unsafe(type)]' which is used when a method has no marker
-// - Allows default safe fallback, with upwards propagation.
-//
-//! 
+//!
 //! Provides a lint that warns/errors when a function calls a method that is marked with a
 //! particular kind of unsafety.
-//! 
-//! Four attributes are used to allow functions to be marked.
+//!
+//! The lint is run by the `tag-safe-driver` binary (a wrapper around `rustc_driver`, which registers the attributes
+//! below), usually invoked over a whole workspace using `cargo tag-safe`. See the README for details.
+//!
+//! Functions are marked using these attributes
 //! - `#[not_safe(tags)]` - Marks a function as not being safe for the given tags
 //! - `#[is_safe(tags)]` - Marks the function as being safe for the given tags (despite what it does internally)
-//! - `#[tagged_safe(tag="file")]` Loads a list of tagged functions for an extern crate from a file.
+//! - `#[tagged_safe(tag="file")]` Loads a list of tagged functions for an extern crate from a file (see `taglist`).
 //! - `#[req_safe(tags)]` - Enables linting this function for use the given tags
 //!
+//! Options are set with `#![tag_safe_config(...)]` on the crate root or in a `tag_safe.toml` file (see `config`).
+//!
 #![crate_name="tag_safe"]
-#![crate_type="dylib"]
-#![feature(plugin_registrar, rustc_private)]
+#![feature(rustc_private)]
 
 #[macro_use]
 extern crate log;
@@ -28,37 +29,47 @@ extern crate log;
 #[macro_use]
 extern crate lazy_static;
 
+extern crate serde;
+#[macro_use]
+extern crate serde_derive;
+extern crate toml;
+
 extern crate syntax;
-extern crate syntax_expand;
 #[macro_use]
 extern crate rustc;
-extern crate rustc_driver;
+extern crate rustc_errors;
 
 mod prescan;
 mod check;
+mod config;
 mod database;
+mod export;
+mod mir;
+mod paths;
+mod sidecar;
+mod taglist;
 
-#[plugin_registrar]
-pub fn plugin_registrar(reg: &mut ::rustc_driver::plugin::Registry) {
-    use syntax::feature_gate::AttributeType;
-    use syntax_expand::base::{SyntaxExtension,SyntaxExtensionKind};
-    use syntax::source_map::edition::Edition;
-    use syntax::symbol::Symbol;
-    
-    reg.register_syntax_extension(Symbol::intern("tagged_safe"), {
-            let v = SyntaxExtension::default(SyntaxExtensionKind::LegacyAttr( Box::new(prescan::HandlerTaggedSafe) ), Edition::Edition2015);
-            v
-            });
+/// Attributes that the driver registers (via `#![register_attr]`) so they can be used without a feature gate
+pub const ATTRIBUTES: &[&str] = &["is_safe", "not_safe", "req_safe", "tagged_safe", "tag_safe_config"];
 
-    let pass = Box::new(check::Pass::new());
-    //reg.register_syntax_extension(intern("is_safe" ), SyntaxExtension::MultiDecorator(Box::new(prescan::HandlerIsSafe) ) );
-    //reg.register_syntax_extension(intern("not_safe"), SyntaxExtension::MultiDecorator(Box::new(prescan::HandlerNotSafe)) );
-    //reg.register_syntax_extension(intern("req_safe"), SyntaxExtension::MultiModifier(Box::new(prescan::HandlerReqSafe)) );
+/// Register the lints and the late lint pass with the compiler's lint store
+///
+/// Intended to be called from `rustc_interface::interface::Config::register_lints`
+pub fn register_lints(_sess: &::rustc::session::Session, lint_store: &mut ::rustc::lint::LintStore) {
+    use rustc::lint::LintPass;
+
+    lint_store.register_lints(&check::Pass::new().get_lints());
+    lint_store.register_late_pass(|| Box::new(check::Pass::new()));
+}
 
-    reg.register_attribute(pass.sym_issafe .clone(), AttributeType::Whitelisted);
-    reg.register_attribute(pass.sym_notsafe.clone(), AttributeType::Whitelisted);
-    reg.register_attribute(pass.sym_reqsafe.clone(), AttributeType::Whitelisted);
-    reg.register_late_lint_pass(pass);
+/// Load the tag lists referenced by `#[tagged_safe]` attributes in the (expanded) crate, and the workspace's
+/// `tag_safe.toml` (if any)
+///
+/// Must be called before the lint pass runs (i.e. after expansion, before analysis)
+pub fn load_tag_lists(sess: &::rustc::session::Session, krate: &syntax::ast::Crate) {
+    // Loaded first, so lists from attributes are merged over those from the file
+    config::load_config_file(sess);
+    prescan::load_tag_lists(sess, krate);
 }
 
 // vim: ts=4 expandtab sw=4
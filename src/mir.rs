@@ -0,0 +1,304 @@
+//! MIR-based backend
+//!
+//! Builds the call graph from the `Call` and `Drop` terminators of each instance's optimised MIR, so drop glue
+//! and calls made by monomorphised generic code are seen exactly as they will be compiled.
+//!
+//! Selected with `#![tag_safe_config(backend = "mir")]`. Results for non-generic functions are stored in the
+//! same cache as the HIR backend, generic instances are only cached for the duration of a check.
+//!
+//! Tagged function pointer slots aren't supported: with `fn_pointers` set, every call through a function pointer
+//! is reported (stores into tagged slots are still checked by the main pass).
+//!
+//! Generic instances nested more than `MAX_DEPTH` deep aren't checked, and use the tag's `extern_policy` instead.
+use std::collections::{HashMap, HashSet};
+use rustc::hir::def_id::DefId;
+use rustc::lint::LateContext;
+use rustc::mir::{self, TerminatorKind};
+use rustc::ty::{self, TyCtxt};
+use rustc::ty::adjustment::PointerCast;
+use rustc::ty::subst::{InternalSubsts, SubstsRef};
+use rustc::ty::fold::TypeFoldable;
+use syntax::source_map::Span;
+use check::{Pass, Violation, report_span, report_assumed, resolve_item, is_format_args, is_generic_callable_call};
+use database::{Tag, Reason, ExternPolicy};
+
+/// Limit on the depth of nested generic instances (to handle polymorphic recursion)
+const MAX_DEPTH: usize = 64;
+
+/// Check the body of a `#[req_safe]` function, calling `cb` for each call to a non-safe function
+pub fn check_fn<F>(pass: &mut Pass, cx: &LateContext, did: DefId, tag: Tag, mut cb: F)
+where
+	F: FnMut(&Violation)
+{
+	let instance = ty::Instance::new(did, InternalSubsts::identity_for_item(cx.tcx, did));
+	let mut c = Checker { pass: pass, cx: cx, tag: tag, root: true, generic_depth: 0, stack: Vec::new(), cycle_low: usize::max_value(), cache: HashMap::new(), walked: HashSet::new() };
+	c.walk_body(instance, &mut cb);
+}
+
+/// Determine if a (local) function is safe by checking its MIR, returning why it isn't safe
+pub fn item_is_safe(pass: &mut Pass, cx: &LateContext, did: DefId, tag: Tag) -> Result<(), Reason>
+{
+	let instance = ty::Instance::new(did, InternalSubsts::identity_for_item(cx.tcx, did));
+	let mut c = Checker { pass: pass, cx: cx, tag: tag, root: false, generic_depth: 0, stack: Vec::new(), cycle_low: usize::max_value(), cache: HashMap::new(), walked: HashSet::new() };
+	let mut reason = None;
+	c.walk_body(instance, &mut |v: &Violation| if reason.is_none() { reason = Some(v.reason()); });
+	match reason
+	{
+	Some(r) => Err(r),
+	None => Ok( () ),
+	}
+}
+
+struct Checker<'a, 'b: 'a, 'tcx: 'b>
+{
+	pass: &'a mut Pass,
+	cx: &'a LateContext<'b, 'tcx>,
+	tag: Tag,
+	// Checking the function being linted, where assumed extern functions are reported
+	root: bool,
+	// Number of generic instances being checked (i.e. not part of the body being checked)
+	generic_depth: usize,
+	// Generic instances currently being checked
+	stack: Vec<ty::Instance<'tcx>>,
+	// Lowest stack position reached by recursion while checking the current instance
+	cycle_low: usize,
+	// Results for generic instances
+	cache: HashMap<ty::Instance<'tcx>, bool>,
+	// Closures (and generators) walked as part of the function that created them
+	walked: HashSet<ty::Instance<'tcx>>,
+}
+
+impl<'a, 'b, 'tcx> Checker<'a, 'b, 'tcx>
+{
+	fn walk_body<F>(&mut self, instance: ty::Instance<'tcx>, cb: &mut F)
+	where
+		F: FnMut(&Violation)
+	{
+		let tcx = self.cx.tcx;
+		let body = tcx.instance_mir(instance.def);
+		let param_env = param_env_for(tcx, instance);
+		// Closures (and generators) are treated as being run by the function that creates them, so they're walked
+		// before any calls to them are seen (and those calls are then skipped)
+		for bb in body.basic_blocks()
+		{
+			for stmt in &bb.statements
+			{
+				let assign = match stmt.kind
+					{
+					mir::StatementKind::Assign(ref assign) => assign,
+					_ => continue,
+					};
+				match assign.1
+				{
+				mir::Rvalue::Aggregate(ref kind, _) =>
+					match **kind
+					{
+					mir::AggregateKind::Closure(did, substs) | mir::AggregateKind::Generator(did, substs, _) => {
+						let substs = tcx.subst_and_normalize_erasing_regions(instance.substs, param_env, &substs);
+						self.walk_nested(ty::Instance::new(did, substs), cb);
+						},
+					_ => {},
+					},
+				// `format_args!` passes the formatting trait methods (e.g. `Display::fmt`) as function pointers
+				mir::Rvalue::Cast(mir::CastKind::Pointer(PointerCast::ReifyFnPointer), ref op, _) if is_format_args(&stmt.source_info.span) => {
+					let ty = tcx.subst_and_normalize_erasing_regions(instance.substs, param_env, &op.ty(body, tcx));
+					if let ty::FnDef(did, substs) = ty.kind {
+						self.check_call(param_env, stmt.source_info.span, did, substs, cb);
+					}
+					},
+				_ => {},
+				}
+			}
+		}
+
+		for bb in body.basic_blocks()
+		{
+			let term = bb.terminator();
+			let span = term.source_info.span;
+			match term.kind
+			{
+			TerminatorKind::Call { ref func, .. } => {
+				let func_ty = tcx.subst_and_normalize_erasing_regions(instance.substs, param_env, &func.ty(body, tcx));
+				match func_ty.kind
+				{
+				// Closures and functions passed to a generic parameter are checked where they're created (or passed)
+				ty::FnDef(did, substs) if is_generic_callable_call(tcx, did, substs) => {},
+				ty::FnDef(did, substs) => self.check_call(param_env, span, did, substs, cb),
+				// The origin of a pointer isn't tracked in MIR (so tagged slots aren't supported), these are only
+				// allowed when not checking pointers
+				ty::FnPtr(_) => {
+					if ::database::CACHE.read().unwrap().tag_options(self.tag).fn_pointers {
+						report(tcx, cb, span, None, "call through a function pointer");
+					}
+					},
+				_ => {},
+				}
+				},
+			TerminatorKind::Drop { ref location, .. } | TerminatorKind::DropAndReplace { ref location, .. } => {
+				let ty = tcx.subst_and_normalize_erasing_regions(instance.substs, param_env, &location.ty(body, tcx).ty);
+				let glue = ty::Instance::resolve_drop_in_place(tcx, ty);
+				if !self.checked(span, |c| c.instance_is_safe(glue)) {
+					report(tcx, cb, span, None, "drop");
+				}
+				},
+			_ => {},
+			}
+		}
+	}
+
+	/// Check a call to a function item
+	fn check_call<F>(&mut self, param_env: ty::ParamEnv<'tcx>, span: Span, did: DefId, substs: SubstsRef<'tcx>, cb: &mut F)
+	where
+		F: FnMut(&Violation)
+	{
+		let tcx = self.cx.tcx;
+		let is_safe = self.checked(span, |c| match ty::Instance::resolve(tcx, param_env, did, substs)
+			{
+			Some(callee) => c.instance_is_safe(callee),
+			None => c.pass.unresolved_is_safe(c.cx, did, c.tag),
+			});
+		if !is_safe {
+			report(tcx, cb, span, Some(resolve_item(tcx, param_env, did, substs)), "call");
+		}
+	}
+
+	/// Walk the body of a closure as if it was part of the current function
+	fn walk_nested<F>(&mut self, instance: ty::Instance<'tcx>, cb: &mut F)
+	where
+		F: FnMut(&Violation)
+	{
+		if self.stack.contains(&instance) || self.stack.len() >= MAX_DEPTH {
+			return ;
+		}
+		self.walked.insert(instance);
+		self.stack.push(instance);
+		self.walk_body(instance, cb);
+		self.stack.pop();
+	}
+
+	/// Run a check, reporting any extern functions that it assumed were safe if it's in the function being linted
+	fn checked<T, C: FnOnce(&mut Self) -> T>(&mut self, span: Span, check: C) -> T
+	{
+		if !self.root || self.generic_depth > 0 {
+			// Left for the function being linted to report
+			return check(self);
+		}
+		let mark = self.pass.assumption_mark();
+		let rv = check(self);
+		let assumed = self.pass.take_assumptions(mark);
+		report_assumed(self.cx, report_span(self.cx.tcx, &span, None).0, &assumed);
+		rv
+	}
+
+	fn instance_is_safe(&mut self, instance: ty::Instance<'tcx>) -> bool
+	{
+		let tcx = self.cx.tcx;
+		// Closures that have been walked where they were created were already checked (and reported) there
+		if let Some(closure) = closure_body(tcx, instance) {
+			if self.walked.contains(&closure) {
+				return true;
+			}
+		}
+		match instance.def
+		{
+		// Non-generic functions are cached by the main pass (which calls back into this backend for local items)
+		ty::InstanceDef::Item(did) if !is_generic(instance) => self.pass.method_is_safe(self.cx, did, self.tag),
+		ty::InstanceDef::Item(did) => {
+			if let Some(v) = self.pass.explicit_tag(&tcx, did, self.tag) {
+				return v;
+			}
+			if !tcx.is_mir_available(did) {
+				return self.pass.method_is_safe(self.cx, did, self.tag);
+			}
+			self.generic_is_safe(instance)
+			},
+		ty::InstanceDef::Virtual(did, _) => self.pass.virtual_is_safe(self.cx, did, self.tag),
+		ty::InstanceDef::Intrinsic(_) => true,
+		// Types without drop glue
+		ty::InstanceDef::DropGlue(_, None) => true,
+		// Other shims (drop glue, closure shims, ...) have MIR that calls the real functions
+		_ => self.generic_is_safe(instance),
+		}
+	}
+
+	fn generic_is_safe(&mut self, instance: ty::Instance<'tcx>) -> bool
+	{
+		if let Some(&v) = self.cache.get(&instance) {
+			return v;
+		}
+		// If this instance is currently being checked, assume it's valid for now. The result of the instance at
+		// the start of the cycle includes the results of every instance in the cycle (as they are ANDed together
+		// on the way back up the stack), and instances within the cycle aren't cached.
+		if let Some(pos) = self.stack.iter().position(|i| *i == instance) {
+			debug!("Recursion in {:?}, deferring to the start of the cycle", instance);
+			self.cycle_low = ::std::cmp::min(self.cycle_low, pos);
+			return true;
+		}
+		if self.stack.len() >= MAX_DEPTH {
+			// Can't be checked (e.g. polymorphic recursion), so treated like an extern function without tag information
+			let policy = ::database::CACHE.read().unwrap().tag_options(self.tag).extern_policy;
+			warn!("Instance {:?} too deeply nested, using extern policy {:?}", instance, policy);
+			return policy != ExternPolicy::AssumeUnsafe;
+		}
+		let pos = self.stack.len();
+		let saved_low = ::std::mem::replace(&mut self.cycle_low, usize::max_value());
+		self.stack.push(instance);
+		self.generic_depth += 1;
+		let mut is_safe = true;
+		self.walk_body(instance, &mut |_: &Violation| { is_safe = false; });
+		self.generic_depth -= 1;
+		self.stack.pop();
+		let low = ::std::mem::replace(&mut self.cycle_low, saved_low);
+		if low < pos {
+			// Part of a cycle that started further up the stack
+			debug!("{:?} - {} (mir, pending cycle)", instance, is_safe);
+			self.cycle_low = ::std::cmp::min(self.cycle_low, low);
+		}
+		else {
+			debug!("{:?} - {} (mir)", instance, is_safe);
+			self.cache.insert(instance, is_safe);
+		}
+		is_safe
+	}
+}
+
+fn report<F>(tcx: TyCtxt, cb: &mut F, span: Span, callee: Option<DefId>, what: &str)
+where
+	F: FnMut(&Violation)
+{
+	let (span, note) = report_span(tcx, &span, callee);
+	cb(&Violation { span: span, callee: callee, what: what, note: note.as_ref().map(|v| &v[..]) });
+}
+
+/// Get the closure (or generator) body that an instance runs, looking through the `FnOnce` shim
+fn closure_body<'tcx>(tcx: TyCtxt<'tcx>, instance: ty::Instance<'tcx>) -> Option<ty::Instance<'tcx>>
+{
+	match instance.def
+	{
+	ty::InstanceDef::Item(did) if tcx.is_closure(did) => Some(instance),
+	ty::InstanceDef::ClosureOnceShim { .. } =>
+		match instance.substs.type_at(0).kind
+		{
+		ty::Closure(did, substs) => Some(ty::Instance::new(did, substs)),
+		_ => None,
+		},
+	_ => None,
+	}
+}
+
+/// Check if an instance has generic parameters (ignoring lifetimes)
+fn is_generic(instance: ty::Instance) -> bool
+{
+	instance.substs.non_erasable_generics().next().is_some()
+}
+
+fn param_env_for<'tcx>(tcx: TyCtxt<'tcx>, instance: ty::Instance<'tcx>) -> ty::ParamEnv<'tcx>
+{
+	// Only the generic body of a function being checked directly still has its own parameters
+	if instance.substs.needs_subst() {
+		tcx.param_env(instance.def_id()).with_reveal_all()
+	}
+	else {
+		ty::ParamEnv::reveal_all()
+	}
+}
@@ -0,0 +1,277 @@
+//! Names of extern functions, as matched against tag list entries
+//!
+//! A function can be named by any public path to it (including re-exports, e.g. `std::sync::Mutex` for
+//! `std::sync::mutex::Mutex`), by the path printed by rustc, or by `hash:<DefPathHash>` (which doesn't change when
+//! rustc changes how paths are printed). Methods are named as `Type::method` for inherent impls and
+//! `<Type as Trait>::method` for trait impls, using any public path for the type and trait. Generic arguments
+//! are removed from paths (by `normalise`) before they're compared.
+use std::collections::{HashMap, HashSet};
+use syntax::ast;
+use rustc::hir;
+use rustc::hir::def::{DefKind, Res};
+use rustc::hir::def_id::{CrateNum, DefId, CRATE_DEF_INDEX};
+use rustc::ty::{self, TyCtxt};
+
+/// Prefix of entries naming a function by its `DefPathHash`
+pub const HASH_PREFIX: &str = "hash:";
+
+/// Public paths to the items of a crate
+pub type ReexportMap = HashMap<DefId, Vec<String>>;
+
+/// Find every public path to the items of a crate, by walking its public modules
+pub fn reexport_map(tcx: TyCtxt, krate: CrateNum) -> ReexportMap
+{
+	let mut rv = ReexportMap::new();
+	let mut visited = HashSet::new();
+	let root = DefId { krate: krate, index: CRATE_DEF_INDEX };
+	let mut stack = vec![ (root, tcx.crate_name(krate).to_string()) ];
+	visited.insert(root);
+	while let Some((module, prefix)) = stack.pop()
+	{
+		for (ident, res) in public_children(tcx, module)
+		{
+			let did = match res
+				{
+				Res::Def(_, did) => did,
+				_ => continue,
+				};
+			let path = format!("{}::{}", prefix, ident);
+			if let Res::Def(DefKind::Mod, _) = res {
+				// Modules can be re-exported from within themselves
+				if visited.insert(did) {
+					stack.push( (did, path.clone()) );
+				}
+			}
+			rv.entry(did).or_insert_with(Vec::new).push(path);
+		}
+	}
+	debug!("{} public paths in {}", rv.values().map(|v| v.len()).sum::<usize>(), tcx.crate_name(krate));
+	rv
+}
+
+/// Get the public items (including re-exports) of a module
+fn public_children(tcx: TyCtxt, module: DefId) -> Vec<(ast::Ident, Res)>
+{
+	if !module.is_local() {
+		return tcx.item_children(module).iter()
+			.filter(|export| export.vis == ty::Visibility::Public)
+			.map(|export| (export.ident, export.res))
+			.collect();
+	}
+	// The local crate doesn't have metadata yet, so walk the HIR (and the re-exports found by name resolution)
+	let mut rv = Vec::new();
+	{
+		let mut add = |ident: ast::Ident, hir_id: hir::HirId| {
+			let did = tcx.hir().local_def_id(hir_id);
+			if let Some(kind) = tcx.def_kind(did) {
+				rv.push( (ident, Res::Def(kind, did)) );
+			}
+			};
+		let (m, _, _) = tcx.hir().get_module(module);
+		for item_id in m.item_ids.iter()
+		{
+			let item = tcx.hir().expect_item(item_id.id);
+			match item.kind
+			{
+			// Handled by `module_exports`
+			hir::ItemKind::Use(..) | hir::ItemKind::ExternCrate(..) => {},
+			hir::ItemKind::ForeignMod(ref fm) => for fi in fm.items.iter() {
+				if fi.vis.node.is_pub() {
+					add(fi.ident, fi.hir_id);
+				}
+				},
+			_ if item.vis.node.is_pub() => add(item.ident, item.hir_id),
+			_ => {},
+			}
+		}
+	}
+	if let Some(exports) = tcx.module_exports(module) {
+		rv.extend( exports.iter()
+			.filter(|export| export.vis == ty::Visibility::Public)
+			.map(|export| (export.ident, export.res))
+			);
+	}
+	rv
+}
+
+/// Check if a crate's public paths include a function, or the type or trait that it's a method of
+pub fn reexported_by(tcx: TyCtxt, reexports: &ReexportMap, did: DefId) -> bool
+{
+	if reexports.contains_key(&did) {
+		return true;
+	}
+	if let Some(impl_did) = tcx.impl_of_method(did) {
+		let self_reexported = match tcx.type_of(impl_did).kind
+			{
+			ty::Adt(def, _) => reexports.contains_key(&def.did),
+			_ => false,
+			};
+		self_reexported || tcx.impl_trait_ref(impl_did).map(|t| reexports.contains_key(&t.def_id)).unwrap_or(false)
+	}
+	else if let Some(trait_did) = tcx.trait_of_item(did) {
+		reexports.contains_key(&trait_did)
+	}
+	else {
+		false
+	}
+}
+
+/// Get the functions of an extern crate that can be named from other crates: public functions, methods of public
+/// types and traits, and methods in trait impls (including impls in other crates for the types and traits that it
+/// re-exports, which its tag lists apply to)
+pub fn exported_fns(tcx: TyCtxt, krate: CrateNum, reexports: &ReexportMap) -> Vec<DefId>
+{
+	let methods = |did: DefId| tcx.associated_items(did)
+		.filter(|item| item.kind == ty::AssocKind::Method)
+		.map(|item| item.def_id);
+	let mut rv = Vec::new();
+	for &did in reexports.keys()
+	{
+		match tcx.def_kind(did)
+		{
+		Some(DefKind::Fn) => rv.push(did),
+		Some(DefKind::Struct) | Some(DefKind::Enum) | Some(DefKind::Union) =>
+			for &impl_did in tcx.inherent_impls(did).iter() {
+				rv.extend( methods(impl_did) );
+			},
+		Some(DefKind::Trait) => rv.extend( methods(did) ),
+		_ => {},
+		}
+	}
+	let mut crates: Vec<CrateNum> = reexports.keys().map(|did| did.krate).collect();
+	crates.push(krate);
+	crates.sort();
+	crates.dedup();
+	for c in crates
+	{
+		for &impl_did in tcx.all_trait_implementations(c).iter()
+		{
+			rv.extend( methods(impl_did).filter(|&did| c == krate || reexported_by(tcx, reexports, did)) );
+		}
+	}
+	rv.sort();
+	rv.dedup();
+	rv
+}
+
+/// Get the names that a function can be listed as (normalised)
+pub fn item_names(tcx: TyCtxt, reexports: &ReexportMap, did: DefId) -> Vec<String>
+{
+	let mut rv = vec![ normalise(&tcx.def_path_str(did)), hash_name(tcx, did) ];
+	rv.extend( item_paths(tcx, reexports, did).iter().map(|p| normalise(p)) );
+
+	let name = match tcx.def_key(did).disambiguated_data.data.get_opt_name()
+		{
+		Some(v) => v,
+		None => return rv,
+		};
+	if let Some(impl_did) = tcx.impl_of_method(did) {
+		let self_paths = match tcx.type_of(impl_did).kind
+			{
+			ty::Adt(def, _) => item_paths(tcx, reexports, def.did),
+			_ => vec![ tcx.type_of(impl_did).to_string() ],
+			};
+		match tcx.impl_trait_ref(impl_did)
+		{
+		Some(trait_ref) => {
+			let trait_paths = item_paths(tcx, reexports, trait_ref.def_id);
+			for s in &self_paths {
+				for t in &trait_paths {
+					rv.push(normalise(&format!("<{} as {}>::{}", s, t, name)));
+				}
+			}
+			},
+		None => rv.extend( self_paths.iter().map(|s| normalise(&format!("{}::{}", s, name))) ),
+		}
+	}
+	else if let Some(trait_did) = tcx.trait_of_item(did) {
+		rv.extend( item_paths(tcx, reexports, trait_did).iter().map(|t| normalise(&format!("{}::{}", t, name))) );
+	}
+	rv.sort();
+	rv.dedup();
+	rv
+}
+
+/// Get the shortest public path to a function (naming methods through their type and trait), if it has one
+pub fn public_path(tcx: TyCtxt, reexports: &ReexportMap, did: DefId) -> Option<String>
+{
+	let shortest = |did: DefId| reexports.get(&did).and_then(|paths| paths.iter().min_by_key(|p| (p.len(), p.as_str())).cloned());
+	if let Some(path) = shortest(did) {
+		return Some(path);
+	}
+	let name = tcx.def_key(did).disambiguated_data.data.get_opt_name()?;
+	if let Some(impl_did) = tcx.impl_of_method(did) {
+		let self_path = match tcx.type_of(impl_did).kind
+			{
+			ty::Adt(def, _) => shortest(def.did)?,
+			_ => return None,
+			};
+		match tcx.impl_trait_ref(impl_did)
+		{
+		// Traits from other crates (e.g. `Drop`) aren't in the map
+		Some(trait_ref) => {
+			let trait_path = shortest(trait_ref.def_id).unwrap_or_else(|| tcx.def_path_str(trait_ref.def_id));
+			Some(format!("<{} as {}>::{}", self_path, trait_path, name))
+			},
+		None => Some(format!("{}::{}", self_path, name)),
+		}
+	}
+	else if let Some(trait_did) = tcx.trait_of_item(did) {
+		shortest(trait_did).map(|t| format!("{}::{}", t, name))
+	}
+	else {
+		None
+	}
+}
+
+/// Name of an item using its `DefPathHash`
+pub fn hash_name(tcx: TyCtxt, did: DefId) -> String
+{
+	format!("{}{}", HASH_PREFIX, tcx.def_path_hash(did).0.to_hex())
+}
+
+/// Public paths to an item (or the path printed by rustc, if there aren't any)
+fn item_paths(tcx: TyCtxt, reexports: &ReexportMap, did: DefId) -> Vec<String>
+{
+	match reexports.get(&did)
+	{
+	Some(v) => v.clone(),
+	None => vec![ tcx.def_path_str(did) ],
+	}
+}
+
+/// Remove generic arguments from a path, e.g. `<std::vec::Vec<T> as core::ops::Drop>::drop` becomes
+/// `<std::vec::Vec as core::ops::Drop>::drop` and `std::sync::Mutex::<T>::lock` becomes `std::sync::Mutex::lock`
+///
+/// A `<` only starts generic arguments if it follows a name (otherwise it's a `<Type as Trait>` qualifier).
+pub fn normalise(path: &str) -> String
+{
+	let mut rv = String::with_capacity(path.len());
+	let mut chars = path.chars();
+	while let Some(c) = chars.next()
+	{
+		let follows_name = rv.ends_with(|c: char| c.is_alphanumeric() || c == '_') || rv.ends_with("::");
+		if c == '<' && follows_name {
+			if rv.ends_with("::") {
+				let len = rv.len() - 2;
+				rv.truncate(len);
+			}
+			// Skip to the matching `>`
+			let mut depth = 1;
+			while depth > 0
+			{
+				match chars.next()
+				{
+				Some('<') => depth += 1,
+				Some('>') => depth -= 1,
+				Some(_) => {},
+				None => break,
+				}
+			}
+		}
+		else {
+			rv.push(c);
+		}
+	}
+	rv
+}
@@ -1,194 +1,250 @@
+use std::io;
+use std::path::{Path, PathBuf};
 use syntax::ast;
-use syntax::ast::{ItemKind,TraitItemKind,NestedMetaItem,ImplItemKind};
+use syntax::ast::{ItemKind,NestedMetaItem};
 use syntax::ast::{MetaItemKind,LitKind};
-use syntax::source_map::Span;
-use syntax_expand::base::{MultiItemModifier};
-use syntax_expand::base::{ExtCtxt,Annotatable};
+use syntax::source_map::{Span, FileName};
+use syntax::symbol::Symbol;
+use syntax::visit;
+use rustc::session::Session;
+use taglist::{Section, Entry};
 
+/// Environment variable holding extra directories to search for tag lists
+pub const SEARCH_PATH_VAR: &str = "TAG_SAFE_PATH";
 
-#[derive(Default)]
-pub struct HandlerTaggedSafe;
-#[derive(Default)]
-pub struct HandlerIsSafe;
-#[derive(Default)]
-pub struct HandlerNotSafe;
-//#[derive(Default)]
-//pub struct HandlerReqSafe;
-
-
-impl MultiItemModifier for HandlerTaggedSafe
+/// Load the lists named by `#![tagged_safe(crate = "name", ...)]` on the crate root, then walk the expanded crate
+/// looking for `#[tagged_safe]` on `extern crate` items
+pub fn load_tag_lists(sess: &Session, krate: &ast::Crate)
 {
-	fn expand(&self, ecx: &mut ExtCtxt, span: Span, meta_item: &ast::MetaItem, item: Annotatable) -> Vec<Annotatable> {
-
-		let crate_name = match item
-			{
-			//Annotatable::Item(box Item { node: ItemKind::ExternCrate(ref opt_name) }) => {
-			Annotatable::Item(ref i) =>
-				match i.kind
-				{
-				ItemKind::ExternCrate(None) => i.ident.name,
-				ItemKind::ExternCrate(Some(crate_name)) => crate_name,
-				_ => return vec![item],
-				},
-			_ => return vec![item],
-			};
-		let mut lh = ::database::CACHE.write().expect("Poisoned lock on tag_safe cache");
-
-		for (tag_name, filename) in get_inner_items(meta_item, "tagged_safe")
-			.filter_map(|ptr| 
-				if let MetaItemKind::NameValue( ast::Lit { kind: LitKind::Str(ref value, _), .. } ) = ptr.kind {
-					Some( (ptr.ident().unwrap().name, value) )
-				}
-				else {
-					warn!("");
-					None
-				}
-				)
+	let mut v = TaggedSafeVisitor {
+		sess: sess,
+		sym_taggedsafe: Symbol::intern("tagged_safe"),
+		};
+	for attr in krate.attrs.iter().filter(|a| a.check_name(v.sym_taggedsafe))
+	{
+		match attr.meta()
 		{
-			let tag = lh.get_tag_or_add(&tag_name.as_str());
-			match lh.load_crate(&crate_name.as_str(), tag, &filename.as_str())
-			{
-			Ok(_) => {},
-			Err(e) => {
-				ecx.span_err(span, &format!("Couldn't open tagging list file from '{}' - {}", filename.as_str(), e));
-				},
-			}
+		Some(meta_item) => v.expand_crate_attr(attr.span, &meta_item),
+		None => sess.span_err(attr.span, "Malformed #[tagged_safe] attribute"),
 		}
-		vec![item]
 	}
+	visit::walk_crate(&mut v, krate);
 }
 
-fn get_fn_node_id(name: &'static str, item: &Annotatable) -> Option<ast::NodeId> {
-	match *item
-	{
-	Annotatable::Item(ref i) =>
-		match i.kind
-		{
-		ItemKind::Fn(..) => Some(i.id),
-		_ => {
-			warn!("#[{}] on non-fn - {:?}", name, i);
-			None
-			},
-		},
-	Annotatable::TraitItem(ref i) =>
-		match i.kind
-		{
-		TraitItemKind::Method(..) => Some(i.id),
-		_ => {
-			warn!("#[{}] on non-fn - {:?}", name, i);
-			None
-			},
-		},
-	Annotatable::ImplItem(ref i) =>
-		match i.kind
-		{
-		ImplItemKind::Method(..) => Some(i.id),
-		_ => {
-			warn!("#[{}] on non-fn - {:?}", name, i);
-			None
-			},
-		},
-	Annotatable::ForeignItem(..)
-	| Annotatable::Stmt(..)
-	| Annotatable::Expr(..)
-	| Annotatable::Arm(..)
-	| Annotatable::Field(..)
-	| Annotatable::FieldPat(..)
-	| Annotatable::GenericParam(..)
-	| Annotatable::Param(..)
-	| Annotatable::StructField(..)
-	| Annotatable::Variant(..)
-		=> None,
-	}
+struct TaggedSafeVisitor<'a>
+{
+	sess: &'a Session,
+	sym_taggedsafe: Symbol,
 }
 
-impl MultiItemModifier for HandlerIsSafe
+impl<'a, 'ast> visit::Visitor<'ast> for TaggedSafeVisitor<'a>
 {
-	fn expand(&self, ecx: &mut ExtCtxt, _span: Span, meta_item: &ast::MetaItem, item: Annotatable) -> Vec<Annotatable> {
-		let node_id = match get_fn_node_id("is_safe", &item)
+	fn visit_item(&mut self, i: &'ast ast::Item) {
+		let crate_name = match i.kind
 			{
-			Some(v) => v,
-			None => return vec![item],
+			ItemKind::ExternCrate(None) => Some(i.ident.name),
+			ItemKind::ExternCrate(Some(crate_name)) => Some(crate_name),
+			_ => None,
 			};
-		let mut lh = ::database::CACHE.write().expect("Poisoned lock on tag_safe cache");
-		for tag_name in get_tags(ecx, meta_item, "is_safe")
+		if let Some(crate_name) = crate_name
 		{
-			debug!("#[is_safe] {} - {}", tag_name, node_id);
-			/*let tag = */lh.get_tag_or_add(&tag_name.as_str());
-			//lh.mark(node_id, tag, true);
+			for attr in i.attrs.iter().filter(|a| a.check_name(self.sym_taggedsafe))
+			{
+				match attr.meta()
+				{
+				Some(meta_item) => self.expand(attr.span, &meta_item, crate_name),
+				None => self.sess.span_err(attr.span, "Malformed #[tagged_safe] attribute"),
+				}
+			}
 		}
-		vec![item]
+		visit::walk_item(self, i);
+	}
+	fn visit_mac(&mut self, _mac: &'ast ast::Mac) {
+		// All macros have been expanded by this point
 	}
 }
 
-impl MultiItemModifier for HandlerNotSafe
+impl<'a> TaggedSafeVisitor<'a>
 {
-	fn expand(&self, ecx: &mut ExtCtxt, _span: Span, meta_item: &ast::MetaItem, item: Annotatable) -> Vec<Annotatable> {
-		let node_id = match get_fn_node_id("not_safe", &item)
+	/// Crate-level `#![tagged_safe(crate = "name", ...)]`, for crates that aren't imported with `extern crate`
+	fn expand_crate_attr(&self, span: Span, meta_item: &ast::MetaItem) {
+		let items = match meta_item.meta_item_list()
 			{
 			Some(v) => v,
-			None => return vec![item],
+			None => {
+				self.sess.span_err(span, "#[tagged_safe] must take a list");
+				return ;
+				},
 			};
-		let mut lh = ::database::CACHE.write().expect("Poisoned lock on tag_safe cache");
-		for tag_name in get_tags(ecx, meta_item, "not_safe")
+		let (crate_items, items): (Vec<_>, Vec<_>) = items.iter().cloned().partition(|item| item.check_name(::syntax::symbol::kw::Crate));
+		let crate_name = match &crate_items[..]
+			{
+			[item] => match item.value_str()
+				{
+				Some(v) => v,
+				None => {
+					self.sess.span_err(item.span(), "Expected `crate = \"name\"` in #![tagged_safe]");
+					return ;
+					},
+				},
+			[] => {
+				self.sess.span_err(span, "#![tagged_safe] on the crate must name the crate the lists are for, e.g. `crate = \"std\"`");
+				return ;
+				},
+			_ => {
+				self.sess.span_err(crate_items[1].span(), "#![tagged_safe] can only name one crate");
+				return ;
+				},
+			};
+		self.load_lists(span, &items, crate_name);
+	}
+
+	fn expand(&self, span: Span, meta_item: &ast::MetaItem, crate_name: Symbol) {
+		match meta_item.meta_item_list()
 		{
-			debug!("#[not_safe] {} - {}", tag_name, node_id);
-			/*let tag = */lh.get_tag_or_add(&tag_name.as_str());
-			//lh.mark(node_id, tag, false);
+		Some(items) => self.load_lists(span, items, crate_name),
+		None => self.sess.span_err(span, "#[tagged_safe] must take a list"),
 		}
-		vec![item]
 	}
-}
 
-//impl MultiItemDecorator for HandlerReqSafe
-//{
-//	fn expand(&self, ecx: &mut ExtCtxt, span: Span, meta_item: &ast::MetaItem, item: Annotatable) -> Vec<Annotatable> {
-//		let node_id = match get_fn_node_id("req_safe", &item)
-//			{
-//			Some(v) => v,
-//			None => return,
-//			};
-//		let mut lh = ::database::CACHE.write().expect("Poisoned lock on tag_safe cache");
-//		for tag_name in get_tags(ecx, meta_item, "req_safe")
-//		{
-//			debug!("#[req_safe] {} - {}", tag_name, node_id);
-//			let tag = lh.get_tag_or_add(tag_name);
-//			lh.mark(node_id, tag, true);
-//		}
-//		vec![item]
-//	}
-//}
+	fn load_lists(&self, span: Span, items: &[NestedMetaItem], crate_name: Symbol) {
+		let mut lh = ::database::CACHE.write().expect("Poisoned lock on tag_safe cache");
 
-fn get_inner_items<'a>(meta_item: &'a ast::MetaItem, attr_name: &'a str) -> impl Iterator<Item=&'a ast::MetaItem>+'a {
-	let it = if let MetaItemKind::List(ref v) = meta_item.kind {
-			v.iter()
-		}
-		else {
-			warn!("Attribute '{}' must take a list", attr_name);
-			[].iter()
-		};
-	it.filter_map(|tag_meta|
-		if let &NestedMetaItem::MetaItem(ref ptr) = tag_meta {
-			Some(ptr)
-		}
-		else {
-			warn!("");
-			None
+		// `tag="file"` loads one tag from a list, a bare `"file"` loads every tag in a version 2 list, and
+		// `tag(...)` gives the entries for a tag inline. These are merged in order, so later ones take priority.
+		for item in items
+		{
+			let (tag_name, filename) = match *item
+				{
+				NestedMetaItem::MetaItem(ref ptr) => match ptr.kind
+					{
+					MetaItemKind::NameValue( ast::Lit { kind: LitKind::Str(ref value, _), .. } ) => (Some(ptr.ident().unwrap().name), *value),
+					MetaItemKind::List(ref entries) => {
+						if let Some(section) = self.inline_section(ptr, entries) {
+							let source = self.sess.source_map().span_to_filename(ptr.span).to_string();
+							for c in lh.add_section(&crate_name.as_str(), &source, Some(ptr.span), section) {
+								self.sess.span_warn(ptr.span, &c);
+							}
+						}
+						continue ;
+						},
+					_ => {
+						self.sess.span_err(ptr.span, "Expected `tag=\"file\"` or `tag(...)` in #[tagged_safe]");
+						continue ;
+						},
+					},
+				NestedMetaItem::Literal( ast::Lit { kind: LitKind::Str(ref value, _), .. } ) => (None, *value),
+				NestedMetaItem::Literal(ref l) => {
+					self.sess.span_err(l.span, "Expected a string in #[tagged_safe]");
+					continue ;
+					},
+				};
+			let tag_name = tag_name.map(|v| v.as_str());
+			let rv = find_list(self.sess, span, &filename.as_str())
+				.and_then(|path| {
+					let conflicts = lh.load_crate(&crate_name.as_str(), tag_name.as_ref().map(|v| &**v), &path.to_string_lossy(), Some(span))?;
+					track_file(self.sess, &path);
+					Ok(conflicts)
+					});
+			match rv
+			{
+			Ok(conflicts) => for c in conflicts {
+				self.sess.span_warn(span, &c);
+				},
+			Err(e) => self.sess.span_err(span, &format!("Couldn't open tagging list file from '{}' - {}", filename.as_str(), e)),
+			}
 		}
-		)
-}
+	}
 
-fn get_tags<'a>(_cx: &'a ExtCtxt, meta_item: &'a ast::MetaItem, attr_name: &'a str) -> impl Iterator<Item=::syntax::symbol::Symbol>+'a {
-	get_inner_items(meta_item, attr_name)
-		.filter_map(|ptr|
-			match (&ptr.kind, ptr.ident())
+	/// Parse inline entries for a tag, `tag(default = true, safe("path", ...), not_safe("path", ...))`
+	///
+	/// Entries are the same as in a tag list (`safe`/`not_safe` are used as `unsafe` can't be used in an attribute),
+	/// with line numbers from the source file.
+	fn inline_section(&self, meta_item: &ast::MetaItem, items: &[NestedMetaItem]) -> Option<Section> {
+		let tag_name = meta_item.name_or_empty().as_str().to_string();
+		let mut section = Section { tag: Some(tag_name), default: None, entries: Vec::new() };
+		let mut is_valid = true;
+		for item in items
+		{
+			let mi = match item.meta_item()
+				{
+				Some(v) => v,
+				None => {
+					self.sess.span_err(item.span(), "Expected `default = true/false`, `safe(...)` or `not_safe(...)`");
+					is_valid = false;
+					continue ;
+					},
+				};
+			match (&*mi.name_or_empty().as_str(), &mi.kind)
 			{
-			(&MetaItemKind::Word, Some(i)) => Some(i.name),
-			_ => {
-				warn!("");
-				None
+			("default", &MetaItemKind::NameValue( ast::Lit { kind: LitKind::Bool(v), .. } )) => section.default = Some(v),
+			(name @ "safe", &MetaItemKind::List(ref paths)) | (name @ "not_safe", &MetaItemKind::List(ref paths)) => {
+				for p in paths.iter()
+				{
+					match *p
+					{
+					NestedMetaItem::Literal( ast::Lit { kind: LitKind::Str(ref value, _), span, .. } ) => {
+						let line = self.sess.source_map().lookup_char_pos(span.lo()).line;
+						section.entries.push(Entry { pattern: value.as_str().to_string(), is_safe: name == "safe", line: line, comment: None });
+						},
+					_ => {
+						self.sess.span_err(p.span(), "Expected a path as a string");
+						is_valid = false;
+						},
+					}
 				}
+				},
+			_ => {
+				self.sess.span_err(mi.span, "Expected `default = true/false`, `safe(...)` or `not_safe(...)`");
+				is_valid = false;
+				},
 			}
-			)
+		}
+		if is_valid { Some(section) } else { None }
+	}
+}
+
+/// Find a tag list named in an attribute
+///
+/// Relative paths are searched for in the directory of the file containing the attribute (like `include_str!`), the
+/// package directory (`CARGO_MANIFEST_DIR`), each directory in `TAG_SAFE_PATH`, then the current directory.
+fn find_list(sess: &Session, span: Span, filename: &str) -> io::Result<PathBuf>
+{
+	let path = Path::new(filename);
+	if path.is_absolute() {
+		return Ok(path.to_owned());
+	}
+	let mut dirs = Vec::new();
+	if let FileName::Real(ref source) = sess.source_map().span_to_filename(span) {
+		if let Some(dir) = source.parent() {
+			dirs.push(dir.to_owned());
+		}
+	}
+	if let Some(dir) = ::std::env::var_os("CARGO_MANIFEST_DIR") {
+		dirs.push(PathBuf::from(dir));
+	}
+	if let Some(v) = ::std::env::var_os(SEARCH_PATH_VAR) {
+		dirs.extend(::std::env::split_paths(&v));
+	}
+	dirs.push(PathBuf::new());
+
+	match dirs.iter().map(|d| d.join(path)).find(|p| p.is_file())
+	{
+	Some(v) => {
+		debug!("Found '{}' at {}", filename, v.display());
+		Ok(v)
+		},
+	None => {
+		let searched: Vec<_> = dirs.iter().map(|d| if d.as_os_str().is_empty() { ".".to_string() } else { d.display().to_string() }).collect();
+		Err(io::Error::new(io::ErrorKind::NotFound, format!("not found (searched {})", searched.join(", "))))
+		},
+	}
 }
 
+/// Add a file that was read to the source map, so it's listed in the dep-info and changes to it cause a rebuild
+pub fn track_file(sess: &Session, path: &Path)
+{
+	if let Err(e) = sess.source_map().load_file(path) {
+		warn!("Couldn't add '{}' to the dependencies - {}", path.display(), e);
+	}
+}
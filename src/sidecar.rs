@@ -0,0 +1,114 @@
+//! Analysis results stored next to a crate's rlib/rmeta, so they're used automatically by dependent crates
+//!
+//! Functions are identified by the hex form of their `DefPathHash` (which is stable between compilations), so the
+//! file is only useful to the compiler. The file is named after the crate's output file, e.g. `foo-1234abcd.tag_safe`
+//! for `libfoo-1234abcd.rlib`.
+use std::collections::HashMap;
+use std::io::{self, BufRead, Write};
+use std::path::{Path, PathBuf};
+use rustc::hir::def_id::{CrateNum, DefId, LOCAL_CRATE};
+use rustc::ty::TyCtxt;
+
+const HEADER: &str = "#!tag_safe results v1";
+const EXTENSION: &str = "tag_safe";
+
+/// Results for an extern crate
+#[derive(Default)]
+pub struct Sidecar
+{
+	pub filename: String,
+	// tag name -> function hash -> is safe
+	tags: HashMap<String, HashMap<String, bool>>,
+}
+
+impl Sidecar
+{
+	/// Load the results for an extern crate (if it was checked)
+	pub fn load_for(tcx: TyCtxt, krate: CrateNum) -> Option<Sidecar>
+	{
+		let path = crate_path(tcx, krate)?;
+		match Self::load(&path)
+		{
+		Ok(v) => {
+			debug!("Loaded results for {} from {}", tcx.crate_name(krate), path.display());
+			Some(v)
+			},
+		Err(ref e) if e.kind() == io::ErrorKind::NotFound => None,
+		Err(e) => {
+			warn!("Couldn't load results from '{}' - {}", path.display(), e);
+			None
+			},
+		}
+	}
+
+	fn load(path: &Path) -> io::Result<Sidecar>
+	{
+		let fp = io::BufReader::new(::std::fs::File::open(path)?);
+		let mut rv = Sidecar { filename: path.display().to_string(), ..Default::default() };
+		let mut lines = fp.lines();
+		if lines.next().transpose()?.as_ref().map(|l| l.trim()) != Some(HEADER) {
+			return Err(io::Error::new(io::ErrorKind::InvalidData, "Missing header"));
+		}
+		let mut cur = None;
+		for line in lines
+		{
+			let line = line?;
+			let line = line.trim();
+			if line.starts_with('[') && line.ends_with(']') {
+				cur = Some(rv.tags.entry(line[1 .. line.len()-1].to_string()).or_insert_with(HashMap::new));
+				continue ;
+			}
+			let mut it = line.split_whitespace();
+			match (it.next(), it.next(), cur.as_mut())
+			{
+			(Some(hash), Some("safe"), Some(map)) => { map.insert(hash.to_string(), true); },
+			(Some(hash), Some("unsafe"), Some(map)) => { map.insert(hash.to_string(), false); },
+			(None, _, _) => {},
+			_ => return Err(io::Error::new(io::ErrorKind::InvalidData, format!("Malformed line `{}`", line))),
+			}
+		}
+		Ok(rv)
+	}
+
+	pub fn get(&self, tcx: TyCtxt, tag_name: &str, did: DefId) -> Option<bool>
+	{
+		self.tags.get(tag_name)?.get(&key(tcx, did)).cloned()
+	}
+
+	pub fn tag_names(&self) -> impl Iterator<Item=&str>
+	{
+		self.tags.keys().map(|k| &k[..])
+	}
+}
+
+/// Write the results for the local crate, as a list of (tag name, [(function, is safe)])
+pub fn write(tcx: TyCtxt, results: &[(String, Vec<(DefId, bool)>)]) -> io::Result<PathBuf>
+{
+	let path = tcx.output_filenames(LOCAL_CRATE).with_extension(EXTENSION);
+	let mut fp = io::BufWriter::new(::std::fs::File::create(&path)?);
+	writeln!(fp, "{}", HEADER)?;
+	for &(ref tag_name, ref fns) in results
+	{
+		writeln!(fp, "[{}]", tag_name)?;
+		for &(did, is_safe) in fns
+		{
+			writeln!(fp, "{} {}", key(tcx, did), if is_safe { "safe" } else { "unsafe" })?;
+		}
+	}
+	Ok(path)
+}
+
+fn key(tcx: TyCtxt, did: DefId) -> String
+{
+	tcx.def_path_hash(did).0.to_hex()
+}
+
+/// Get the path of the results for an extern crate (next to its rlib/rmeta)
+fn crate_path(tcx: TyCtxt, krate: CrateNum) -> Option<PathBuf>
+{
+	let source = tcx.used_crate_source(krate);
+	let lib = source.rmeta.as_ref().or(source.rlib.as_ref()).or(source.dylib.as_ref())?;
+	let stem = lib.0.file_stem()?.to_str()?;
+	let stem = if stem.starts_with("lib") { &stem[3..] } else { stem };
+	Some(lib.0.with_file_name(format!("{}.{}", stem, EXTENSION)))
+}
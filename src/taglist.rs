@@ -0,0 +1,186 @@
+//! Tag list files, used to annotate the functions of extern crates
+//!
+//! Version 1 lists hold a single tag, version 2 lists (starting with a `#!tag_safe v2` header) have a section for
+//! each tag. See the README for the format, and `paths` for the names a function can be listed as.
+use std::io;
+
+pub const V2_HEADER: &str = "#!tag_safe v2";
+
+pub struct TagList
+{
+	pub sections: Vec<Section>,
+}
+
+/// The entries for a single tag
+pub struct Section
+{
+	/// Tag name (`None` for version 1 lists, where the tag is given by the `#[tagged_safe]` attribute)
+	pub tag: Option<String>,
+	/// Status of functions that don't match any entry (`None` if not given, which is safe unless set by another list)
+	pub default: Option<bool>,
+	pub entries: Vec<Entry>,
+}
+
+pub struct Entry
+{
+	pub pattern: String,
+	pub is_safe: bool,
+	/// Line number in the file (for diagnostics)
+	pub line: usize,
+	/// Comment written after the entry
+	pub comment: Option<String>,
+}
+
+impl TagList
+{
+	pub fn load(filename: &str) -> Result<TagList, io::Error>
+	{
+		let text = ::std::fs::read_to_string(filename)?;
+		Self::parse(&text)
+	}
+
+	pub fn parse(text: &str) -> Result<TagList, io::Error>
+	{
+		let mut lines = text.lines().enumerate().map(|(i, l)| (i + 1, l.trim()));
+		match lines.next()
+		{
+		Some((_, V2_HEADER)) => Self::parse_v2(lines),
+		Some((_, "true")) => Ok(Self::parse_v1(true, lines)),
+		Some((_, "false")) => Ok(Self::parse_v1(false, lines)),
+		_ => Err(invalid_data(1, "Expected `#!tag_safe v2`, or `true`/`false` for a version 1 list")),
+		}
+	}
+
+	fn parse_v1<'a>(default: bool, lines: impl Iterator<Item=(usize, &'a str)>) -> TagList
+	{
+		let entries = lines
+			.filter(|&(_, l)| l != "")
+			.map(|(line, l)| Entry { pattern: l.to_string(), is_safe: !default, line: line, comment: None })
+			.collect();
+		TagList {
+			sections: vec![ Section { tag: None, default: Some(default), entries: entries } ],
+			}
+	}
+
+	fn parse_v2<'a>(lines: impl Iterator<Item=(usize, &'a str)>) -> Result<TagList, io::Error>
+	{
+		let mut sections: Vec<Section> = Vec::new();
+		for (line, l) in lines
+		{
+			let l = match l.find('#')
+				{
+				Some(pos) => l[..pos].trim(),
+				None => l,
+				};
+			if l == "" {
+				continue ;
+			}
+			if l.starts_with('[') && l.ends_with(']') {
+				let tag = l[1 .. l.len()-1].trim();
+				if sections.iter().any(|s| s.tag.as_ref().map(|t| t == tag).unwrap_or(false)) {
+					return Err(invalid_data(line, &format!("Duplicate section for tag `{}`", tag)));
+				}
+				sections.push(Section { tag: Some(tag.to_string()), default: None, entries: Vec::new() });
+				continue ;
+			}
+
+			let section = match sections.last_mut()
+				{
+				Some(s) => s,
+				None => return Err(invalid_data(line, "Entry before the first `[tag]` section")),
+				};
+			let (key, value) = match l.find(|c: char| c.is_whitespace() || c == '=')
+				{
+				Some(pos) => (&l[..pos], l[pos..].trim_start()),
+				None => (l, ""),
+				};
+			match key
+			{
+			"default" => {
+				section.default = match value.trim_start_matches('=').trim()
+					{
+					"safe" => Some(true),
+					"unsafe" => Some(false),
+					_ => return Err(invalid_data(line, "`default` must be `safe` or `unsafe`")),
+					};
+				},
+			"safe" | "unsafe" if value != "" => {
+				section.entries.push(Entry { pattern: value.to_string(), is_safe: key == "safe", line: line, comment: None });
+				},
+			_ => return Err(invalid_data(line, &format!("Unknown entry `{}`", l))),
+			}
+		}
+		Ok(TagList { sections: sections })
+	}
+}
+
+impl TagList
+{
+	/// Write as a version 2 list
+	pub fn write<W: io::Write>(&self, out: &mut W, comment: &str) -> io::Result<()>
+	{
+		writeln!(out, "{}", V2_HEADER)?;
+		writeln!(out, "# {}", comment)?;
+		for section in &self.sections
+		{
+			writeln!(out)?;
+			writeln!(out, "[{}]", section.tag.as_ref().expect("Writing a tag list section without a tag"))?;
+			writeln!(out, "default = {}", if section.default.unwrap_or(true) { "safe" } else { "unsafe" })?;
+			for e in &section.entries
+			{
+				match e.comment
+				{
+				Some(ref c) => writeln!(out, "{} {}	# {}", if e.is_safe { "safe" } else { "unsafe" }, e.pattern, c)?,
+				None => writeln!(out, "{} {}", if e.is_safe { "safe" } else { "unsafe" }, e.pattern)?,
+				}
+			}
+		}
+		Ok( () )
+	}
+}
+
+impl Entry
+{
+	pub fn is_glob(&self) -> bool
+	{
+		self.pattern.contains('*')
+	}
+	pub fn matches(&self, path: &str) -> bool
+	{
+		glob_match(self.pattern.as_bytes(), path.as_bytes())
+	}
+}
+
+/// Match `text` against `pattern`, where `*` matches any sequence of characters
+fn glob_match(pattern: &[u8], text: &[u8]) -> bool
+{
+	let (mut pi, mut ti) = (0, 0);
+	// Position after the last `*` seen, and the text position it's currently matched up to
+	let mut star = None;
+	while ti < text.len()
+	{
+		if pi < pattern.len() && pattern[pi] == b'*' {
+			pi += 1;
+			star = Some( (pi, ti) );
+		}
+		else if pi < pattern.len() && pattern[pi] == text[ti] {
+			pi += 1;
+			ti += 1;
+		}
+		else if let Some((star_pi, star_ti)) = star {
+			// Extend the `*` by one character and try again
+			pi = star_pi;
+			ti = star_ti + 1;
+			star = Some( (star_pi, ti) );
+		}
+		else {
+			return false;
+		}
+	}
+	pattern[pi..].iter().all(|&c| c == b'*')
+}
+
+fn invalid_data(line: usize, msg: &str) -> io::Error
+{
+	io::Error::new(io::ErrorKind::InvalidData, format!("line {}: {}", line, msg))
+}
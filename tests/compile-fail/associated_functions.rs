@@ -1,5 +1,3 @@
-#![feature(plugin)]
-#![plugin(tag_safe)]
 #![deny(not_tagged_safe)]
 
 #[req_safe(foo)]
@@ -0,0 +1,9 @@
+// no-prefer-dynamic
+#![crate_type="rlib"]
+
+// Never names `irq`, but its results still include it
+extern crate chain_raw;
+
+pub fn helper() {
+	chain_raw::raw();
+}
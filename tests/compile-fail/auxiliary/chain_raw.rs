@@ -0,0 +1,6 @@
+// no-prefer-dynamic
+#![crate_type="rlib"]
+
+#[not_safe(irq)]
+pub fn raw() {
+}
@@ -0,0 +1,27 @@
+// no-prefer-dynamic
+// rustc-env:TAG_SAFE_EXPORT=target/tag_safe_roundtrip
+#![crate_type="rlib"]
+
+#[not_safe(irq)]
+fn raw() {
+}
+
+#[is_safe(irq)]
+pub fn explicitly_safe() {
+}
+
+// Only public through the re-exports below, so exported with those paths
+mod imp {
+	pub fn lock() {
+		super::raw();
+	}
+
+	pub struct Port;
+	impl Port {
+		pub fn write(&self) {
+			super::raw();
+		}
+	}
+}
+
+pub use imp::{lock, Port};
@@ -0,0 +1,15 @@
+// no-prefer-dynamic
+#![crate_type="rlib"]
+
+#[not_safe(irq)]
+pub fn acquire_non_irq_spinlock() {
+}
+
+#[is_safe(irq)]
+pub fn acquire_irq_spinlock() {
+}
+
+// Not tagged, but found to be irq-unsafe when this crate is checked
+pub fn lock_wrapper() {
+	acquire_non_irq_spinlock();
+}
@@ -0,0 +1,25 @@
+// The lint names the callee and explains the chain of calls down to the root cause
+#![allow(dead_code)]
+
+#[not_safe(irq)]	//~ NOTE `root` is marked #[not_safe(irq)] here
+fn root() {
+}
+
+fn middle() {
+	root();	//~ NOTE `middle` calls `root`
+}
+
+fn wrapper() {
+	middle();	//~ NOTE `wrapper` calls `middle`
+}
+
+#[deny(not_tagged_safe)]	//~ NOTE lint level defined here
+#[req_safe(irq)]
+fn handler() {
+	wrapper();
+	//~^ ERROR Calling irq-unsafe method from
+	//~| NOTE calls `wrapper`
+}
+
+fn main() {
+}
@@ -0,0 +1,34 @@
+// compile-flags: --edition=2018
+#![deny(not_tagged_safe)]
+#![allow(dead_code)]
+
+#[not_safe(irq)]
+fn acquire_non_irq_spinlock(_l: &u32) {
+}
+
+#[req_safe(irq)]
+fn irq_handler(items: &[u32]) {
+	items.iter().for_each(|x| acquire_non_irq_spinlock(x));
+	//~^ ERROR Calling irq-unsafe method from
+}
+
+fn wrapper(items: &[u32]) {
+	items.iter().for_each(|x| acquire_non_irq_spinlock(x));
+}
+
+#[req_safe(irq)]
+fn irq_handler_indirect(items: &[u32]) {
+	wrapper(items);
+	//~^ ERROR Calling irq-unsafe method from
+}
+
+#[req_safe(irq)]
+fn irq_handler_async() {
+	let _f = async {
+		acquire_non_irq_spinlock(&0);
+		//~^ ERROR Calling irq-unsafe method from
+	};
+}
+
+fn main() {
+}
@@ -0,0 +1,39 @@
+// Tag options from a `tag_safe.toml` in a parent of the package directory
+// rustc-env:CARGO_MANIFEST_DIR=tests/compile-fail/config_file/pkg
+#![allow(dead_code)]
+
+#[not_safe(irq)]	//~ NOTE `acquire_non_irq_spinlock` is marked #[not_safe(irq)] here
+fn acquire_non_irq_spinlock() {
+}
+
+#[not_safe(quiet)]
+fn noisy() {
+}
+
+// `severity = "deny"` is an error without denying the lint, and the description is added as a note
+#[req_safe(irq)]
+fn irq_handler() {
+	acquire_non_irq_spinlock();
+	//~^ ERROR Calling irq-unsafe method from
+	//~| NOTE calls `acquire_non_irq_spinlock`
+	//~| NOTE `irq`: Safe to call from an interrupt handler
+}
+
+// `severity = "allow"` disables the check
+#[deny(not_tagged_safe)]
+#[req_safe(quiet)]
+fn quiet() {
+	noisy();
+}
+
+#[deny(not_tagged_safe)]	//~ NOTE lint level defined here
+#[req_safe(nostd)]
+fn no_std() -> u32 {
+	::std::process::id()
+	//~^ ERROR Calling nostd-unsafe method from
+	//~| NOTE calls `std::process::id`
+	//~| NOTE `std::process::id` has no tag information, and extern functions are assumed to not be nostd-safe
+}
+
+fn main() {
+}
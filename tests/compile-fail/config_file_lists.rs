@@ -0,0 +1,30 @@
+// Tag lists named by `tag_safe.toml`, and options on the crate root taking priority over the file
+// rustc-env:CARGO_MANIFEST_DIR=tests/compile-fail/config_file/pkg
+#![tag_safe_config(nostd(extern_policy = "assume_safe"))]
+#![allow(dead_code)]
+
+// From `lists`
+#[deny(not_tagged_safe)]
+#[req_safe(print)]
+fn print() {
+	println!("Hello World");
+	//~^ ERROR Calling print-unsafe method from
+}
+
+// From `tags`
+#[deny(not_tagged_safe)]
+#[req_safe(exit)]
+fn exit() {
+	::std::process::exit(0);
+	//~^ ERROR Calling exit-unsafe method from
+}
+
+// `assume_unsafe` in the file is overridden
+#[deny(not_tagged_safe)]
+#[req_safe(nostd)]
+fn no_std() -> u32 {
+	::std::process::id()
+}
+
+fn main() {
+}
@@ -0,0 +1,6 @@
+// Unknown fields in `tag_safe.toml` are errors
+// rustc-env:CARGO_MANIFEST_DIR=tests/compile-fail/config_invalid
+// error-pattern:unknown field `unknown_option`
+
+fn main() {
+}
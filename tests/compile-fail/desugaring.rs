@@ -0,0 +1,64 @@
+// compile-flags: --edition=2018
+#![deny(not_tagged_safe)]
+#![allow(dead_code)]
+
+use std::fmt;
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+struct Ring;
+impl Iterator for Ring {
+	type Item = u32;
+	#[not_safe(irq)]
+	fn next(&mut self) -> Option<u32> { None }
+}
+
+struct AllocError;
+struct KernelError;
+impl From<AllocError> for KernelError {
+	#[not_safe(irq)]
+	fn from(_e: AllocError) -> KernelError { KernelError }
+}
+
+struct Name;
+impl fmt::Display for Name {
+	#[not_safe(irq)]
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result { f.write_str("name") }
+}
+
+struct Event;
+impl Future for Event {
+	type Output = ();
+	#[not_safe(irq)]
+	fn poll(self: Pin<&mut Self>, _cx: &mut Context) -> Poll<()> { Poll::Ready(()) }
+}
+
+#[req_safe(irq)]
+fn irq_for_loop() {
+	for _v in Ring {
+	//~^ ERROR Calling irq-unsafe method from
+	}
+}
+
+#[req_safe(irq)]
+fn irq_try(r: Result<u32, AllocError>) -> Result<u32, KernelError> {
+	let v = r?;
+	//~^ ERROR Calling irq-unsafe method from
+	Ok(v)
+}
+
+#[req_safe(irq)]
+fn irq_format(n: &Name) -> String {
+	format!("{}", n)
+	//~^ ERROR Calling irq-unsafe method from
+}
+
+#[req_safe(irq)]
+async fn irq_await() {
+	Event.await;
+	//~^ ERROR Calling irq-unsafe method from
+}
+
+fn main() {
+}
@@ -0,0 +1,49 @@
+#![tag_safe_config(b(strict_virtual))]
+#![deny(not_tagged_safe)]
+#![allow(dead_code)]
+
+trait Handler {
+	fn handle(&self);
+}
+
+struct SafeHandler;
+impl Handler for SafeHandler {
+	fn handle(&self) {}
+}
+
+struct UnsafeHandler;
+impl Handler for UnsafeHandler {
+	#[not_safe(a)]
+	fn handle(&self) {}
+}
+
+trait TaggedHandler {
+	#[is_safe(a)]
+	fn run(&self);
+}
+
+#[req_safe(a)]
+fn dispatch(h: &dyn Handler, t: &dyn TaggedHandler) {
+	// One of the implementations isn't safe
+	h.handle();
+	//~^ ERROR Calling a-unsafe method from
+	t.run();
+}
+
+trait Callback {
+	fn call(&self);
+}
+struct Noop;
+impl Callback for Noop {
+	fn call(&self) {}
+}
+
+#[req_safe(b)]
+fn strict_dispatch(c: &dyn Callback) {
+	// All implementations are safe, but `b` requires virtual calls to be tagged
+	c.call();
+	//~^ ERROR Calling b-unsafe method from
+}
+
+fn main() {
+}
@@ -0,0 +1,21 @@
+// Tag lists written by `TAG_SAFE_EXPORT` can be loaded by dependent crates
+// aux-build:roundtrip_export.rs
+#[tagged_safe("target/tag_safe_roundtrip/roundtrip_export.tags")]
+extern crate roundtrip_export;
+
+#[deny(not_tagged_safe)]	//~ NOTE lint level defined here
+#[req_safe(irq)]
+fn handler(p: &roundtrip_export::Port) {
+	roundtrip_export::lock();
+	//~^ ERROR Calling irq-unsafe method from
+	//~| NOTE calls `roundtrip_export::lock`
+	//~| NOTE `roundtrip_export::lock` is listed as not irq-safe at
+	p.write();
+	//~^ ERROR Calling irq-unsafe method from
+	//~| NOTE calls `roundtrip_export::Port::write`
+	//~| NOTE `roundtrip_export::Port::write` is listed as not irq-safe at
+	roundtrip_export::explicitly_safe();
+}
+
+fn main() {
+}
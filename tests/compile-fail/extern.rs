@@ -1,7 +1,4 @@
-#![feature(plugin)]
-#![plugin(tag_safe)]
-
-// Path is relative to rustc invocation directory - package root in this case
+// Not next to the source file, so found relative to the package root (CARGO_MANIFEST_DIR)
 #[allow(unused_extern_crates)]
 #[tagged_safe(print="tests/compile-fail/libstd_foo.txt")]
 extern crate std as _std;
@@ -0,0 +1,14 @@
+// Tags on functions in other crates are read from the crate's metadata
+// aux-build:upstream_tags.rs
+extern crate upstream_tags;
+
+#[deny(not_tagged_safe)]
+#[req_safe(irq)]
+fn irq_handler() {
+	upstream_tags::acquire_irq_spinlock();
+	upstream_tags::acquire_non_irq_spinlock();
+	//~^ ERROR Calling irq-unsafe method from
+}
+
+fn main() {
+}
@@ -0,0 +1,14 @@
+// Results are passed along by crates that use a tag without naming it
+// aux-build:chain_raw.rs
+// aux-build:chain_helper.rs
+extern crate chain_helper;
+
+#[deny(not_tagged_safe)]
+#[req_safe(irq)]
+fn irq_handler() {
+	chain_helper::helper();
+	//~^ ERROR Calling irq-unsafe method from
+}
+
+fn main() {
+}
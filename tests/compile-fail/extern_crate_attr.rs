@@ -0,0 +1,14 @@
+// compile-flags: --edition=2018
+// Lists for crates that aren't imported using `extern crate`
+#![tagged_safe(crate = "std", print = "libstd_foo.txt")]
+
+#[req_safe(print)]
+#[deny(not_tagged_safe)]
+fn main() {
+	bar();
+    //~^ ERROR Calling print-unsafe method from
+}
+
+fn bar() {
+	println!("Hello World");
+}
@@ -0,0 +1,13 @@
+// Results from checking a dependency are used for its untagged functions
+// aux-build:upstream_tags.rs
+extern crate upstream_tags;
+
+#[deny(not_tagged_safe)]
+#[req_safe(irq)]
+fn irq_handler() {
+	upstream_tags::lock_wrapper();
+	//~^ ERROR Calling irq-unsafe method from
+}
+
+fn main() {
+}
@@ -0,0 +1,25 @@
+// Tag list entries given inline in the attribute
+#![tagged_safe(crate = "std", print(default = true, not_safe("std::io::_print", "std::thread::sleep")))]
+#![allow(dead_code)]
+
+fn say_hello() {
+	println!("Hello World");
+}
+
+fn nap() {
+	::std::thread::sleep(::std::time::Duration::from_millis(1));
+}
+
+fn yield_now() {
+	::std::thread::yield_now();
+}
+
+#[req_safe(print)]
+#[deny(not_tagged_safe)]
+fn main() {
+	say_hello();
+    //~^ ERROR Calling print-unsafe method from
+	nap();
+    //~^ ERROR Calling print-unsafe method from
+	yield_now();
+}
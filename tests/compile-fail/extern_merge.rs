@@ -0,0 +1,48 @@
+// Lists for the same crate are merged, with later lists overriding earlier ones
+#![allow(dead_code)]
+
+#[allow(unused_extern_crates)]
+#[tagged_safe("libstd_v2.tags")]
+// Inline entries are merged in order with the lists in the same attribute
+#[tagged_safe("libstd_overlay.tags", print(safe("std::thread::sleep")))]
+//~^ WARNING Conflicting `print` entries for crate `std`: `std::io::_print` is unsafe
+//~| WARNING Conflicting `print` entries for crate `std`: `std::thread::sleep` is unsafe
+// Only conflicts with a default set by another list (`[exit]` in libstd_v2.tags doesn't set one)
+#[tagged_safe(exit(default = false))]
+extern crate std as _std;
+
+#[deny(not_tagged_safe)]
+#[req_safe(print)]
+fn print() {
+	// Unsafe in the base list, overridden by the overlay
+	println!("Hello World");
+}
+
+#[deny(not_tagged_safe)]
+#[req_safe(print)]
+fn nap() {
+	// Unsafe in the overlay, overridden by the inline entry
+	::std::thread::sleep(::std::time::Duration::from_millis(1));
+}
+
+#[deny(not_tagged_safe)]
+#[req_safe(print)]
+fn yield_now() {
+	::std::thread::yield_now();
+}
+
+#[deny(not_tagged_safe)]
+#[req_safe(exit)]
+fn get_id() -> u32 {
+	::std::process::id()
+}
+
+#[deny(not_tagged_safe)]
+#[req_safe(exit)]
+fn exit_yield() {
+	::std::thread::yield_now();
+	//~^ ERROR Calling exit-unsafe method from
+}
+
+fn main() {
+}
@@ -0,0 +1,30 @@
+// List entries match re-exported paths and methods without generic arguments
+#![tagged_safe(crate = "std", lock(not_safe("std::sync::Mutex::lock")), swap(not_safe("std::mem::swap", "<std::vec::Vec as std::ops::Drop>::drop")))]
+#![allow(dead_code)]
+
+use std::sync::Mutex;
+
+#[deny(not_tagged_safe)]
+#[req_safe(lock)]
+fn take(m: &Mutex<u32>) -> u32 {
+	*m.lock().unwrap()
+	//~^ ERROR Calling lock-unsafe method from
+}
+
+// Items of `core` that `std` re-exports use the list for `std`
+#[deny(not_tagged_safe)]
+#[req_safe(swap)]
+fn exchange(a: &mut u32, b: &mut u32) {
+	::std::mem::swap(a, b);
+	//~^ ERROR Calling swap-unsafe method from
+}
+
+// As do trait impls (from `alloc`) for the types and traits that it re-exports
+#[deny(not_tagged_safe)]
+#[req_safe(swap)]
+fn discard(_v: Vec<u32>) {
+}
+//~^ ERROR Calling swap-unsafe method from
+
+fn main() {
+}
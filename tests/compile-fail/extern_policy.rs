@@ -0,0 +1,22 @@
+// Policies for extern functions without any tag information
+#![tag_safe_config(irq(extern_policy = "assume_unsafe"), log(extern_policy = "warn"))]
+#![allow(dead_code)]
+
+#[deny(not_tagged_safe)]
+#[req_safe(irq)]
+fn irq_handler() {
+	::std::process::id();
+	//~^ ERROR Calling irq-unsafe method from
+}
+
+#[deny(tag_safe_assumed_extern)]
+#[req_safe(log)]
+fn logger() {
+	::std::process::id();
+	//~^ ERROR Assuming that extern function `std::process::id` is log-safe
+	// Only reported the first time
+	::std::process::id();
+}
+
+fn main() {
+}
@@ -0,0 +1,34 @@
+// Version 2 tag lists, with several tags in one file (found relative to this source file)
+#![allow(dead_code)]
+
+#[allow(unused_extern_crates)]
+#[tagged_safe("libstd_v2.tags")]
+extern crate std as _std;
+
+fn say_hello() {
+	println!("Hello World");
+}
+
+#[deny(not_tagged_safe)]
+#[req_safe(print)]
+fn print() {
+	say_hello();
+	//~^ ERROR Calling print-unsafe method from
+}
+
+#[deny(not_tagged_safe)]
+#[req_safe(exit)]
+fn exit() {
+	::std::process::exit(0);
+	//~^ ERROR Calling exit-unsafe method from
+}
+
+#[deny(not_tagged_safe)]
+#[req_safe(exit)]
+fn get_id() -> u32 {
+	// Matched by the glob, but explicitly listed as safe
+	::std::process::id()
+}
+
+fn main() {
+}
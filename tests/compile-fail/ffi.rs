@@ -0,0 +1,37 @@
+// Tags on foreign functions and `extern` blocks, and the default for untagged foreign functions
+#![tag_safe_config(irq(ffi = "unsafe"))]
+#![allow(dead_code)]
+
+extern "C" {
+	#[not_safe(irq)]
+	fn lock_mutex();
+	#[is_safe(irq)]
+	fn atomic_inc();
+	fn untagged();
+}
+
+// Applies to every function in the block
+#[is_safe(irq)]
+extern "C" {
+	fn get_time() -> u64;
+	#[not_safe(irq)]
+	fn sleep();
+}
+
+#[deny(not_tagged_safe)]
+#[req_safe(irq)]
+fn irq_handler() {
+	unsafe {
+		lock_mutex();
+		//~^ ERROR Calling irq-unsafe method from
+		atomic_inc();
+		untagged();
+		//~^ ERROR Calling irq-unsafe method from
+		get_time();
+		sleep();
+		//~^ ERROR Calling irq-unsafe method from
+	}
+}
+
+fn main() {
+}
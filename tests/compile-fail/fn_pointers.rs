@@ -0,0 +1,64 @@
+#![tag_safe_config(irq(fn_pointers))]
+#![deny(not_tagged_safe)]
+#![allow(dead_code)]
+
+#[not_safe(irq)]
+fn acquire_non_irq_spinlock() {
+}
+
+#[is_safe(irq)]
+fn ack_irq() {
+}
+
+struct Dispatcher {
+	#[req_safe(irq)]
+	handler: fn(),
+	other: fn(),
+}
+
+#[req_safe(irq)]
+static HANDLERS: [fn(); 2] = [ack_irq, acquire_non_irq_spinlock];
+//~^ ERROR Storing a irq-unsafe function in a #[req_safe(irq)] static
+
+fn register(#[req_safe(irq)] _handler: fn()) {
+}
+
+fn setup() -> Dispatcher {
+	register(ack_irq);
+	register(acquire_non_irq_spinlock);
+	//~^ ERROR Storing a irq-unsafe function in a #[req_safe(irq)] parameter
+	let mut d = Dispatcher { handler: ack_irq, other: acquire_non_irq_spinlock };
+	d.handler = acquire_non_irq_spinlock;
+	//~^ ERROR Storing a irq-unsafe function in a #[req_safe(irq)] slot
+	// Pointers must come from another slot with the same tag
+	let p: fn() = acquire_non_irq_spinlock;
+	d.handler = p;
+	//~^ ERROR Storing an untagged function pointer in a #[req_safe(irq)] slot
+	d.handler = d.other;
+	//~^ ERROR Storing an untagged function pointer in a #[req_safe(irq)] slot
+	d.handler = HANDLERS[0];
+	d
+}
+
+fn rebind(d: &mut Dispatcher, handler: fn()) {
+	d.handler = handler;
+	//~^ ERROR Storing an untagged function pointer in a #[req_safe(irq)] slot
+}
+
+fn rebind_tagged(d: &mut Dispatcher, #[req_safe(irq)] handler: fn()) {
+	d.handler = handler;
+}
+
+#[req_safe(irq)]
+fn irq_handler(d: &Dispatcher, i: usize) {
+	(d.handler)();
+	HANDLERS[i]();
+	(d.other)();
+	//~^ ERROR Calling irq-unsafe method from
+	let f = acquire_non_irq_spinlock;
+	f();
+	//~^ ERROR Calling irq-unsafe method from
+}
+
+fn main() {
+}
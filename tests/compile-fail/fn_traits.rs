@@ -0,0 +1,35 @@
+// Calls through the `Fn*` traits on trait objects and generic parameters
+#![tag_safe_config(irq(strict_virtual))]
+#![deny(not_tagged_safe)]
+#![allow(dead_code)]
+
+struct Device {
+	handler: Box<dyn Fn()>,
+}
+
+#[req_safe(irq)]
+fn interrupt(d: &Device) {
+	(d.handler)();
+	//~^ ERROR Calling irq-unsafe method from
+}
+
+#[req_safe(irq)]
+fn call_ref(f: &mut dyn FnMut()) {
+	f();
+	//~^ ERROR Calling irq-unsafe method from
+}
+
+#[req_safe(irq)]
+fn call_generic<F: Fn()>(f: F) {
+	// The closure or function passed in is checked by the caller
+	f();
+}
+
+#[req_safe(irq)]
+fn call_closure() {
+	let c = || ();
+	c();
+}
+
+fn main() {
+}
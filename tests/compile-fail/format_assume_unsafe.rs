@@ -0,0 +1,24 @@
+// Calls made by `format!` are reported once, even when every untagged extern function is assumed unsafe
+#![tag_safe_config(irq(extern_policy = "assume_unsafe"))]
+#![tagged_safe(crate = "alloc", irq(safe("alloc::fmt::format")))]
+#![tagged_safe(crate = "core", irq(default = true, not_safe("core::fmt::Arguments::new_v1")))]
+#![allow(dead_code)]
+
+use std::fmt;
+
+struct Name;
+impl fmt::Display for Name {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		f.write_str("name")
+	}
+}
+
+#[deny(not_tagged_safe)]
+#[req_safe(irq)]
+fn irq_format(n: &Name) -> String {
+	format!("{}", n)
+	//~^ ERROR Calling irq-unsafe method from
+}
+
+fn main() {
+}
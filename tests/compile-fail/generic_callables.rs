@@ -0,0 +1,28 @@
+// Closures and functions passed to a generic higher-order function are checked where they're created or passed
+#![deny(not_tagged_safe)]
+#![allow(dead_code)]
+
+#[not_safe(irq)]
+fn acquire_non_irq_spinlock() {
+}
+
+fn with_lock<F: FnOnce()>(f: F) {
+	f()
+}
+
+#[req_safe(irq)]
+fn irq_closure() {
+	with_lock(|| {
+		acquire_non_irq_spinlock()
+		//~^ ERROR Calling irq-unsafe method from
+	});
+}
+
+#[req_safe(irq)]
+fn irq_fn_item() {
+	with_lock(acquire_non_irq_spinlock);
+	//~^ ERROR Calling irq-unsafe method from
+}
+
+fn main() {
+}
@@ -0,0 +1,81 @@
+#![deny(not_tagged_safe)]
+#![allow(dead_code)]
+
+use std::ops::{Add, Deref, Index};
+
+#[derive(Copy, Clone)]
+struct Counter(u32);
+impl Add for Counter {
+	type Output = Counter;
+	#[not_safe(irq)]
+	fn add(self, other: Counter) -> Counter { Counter(self.0 + other.0) }
+}
+
+struct Guard(u32);
+impl Deref for Guard {
+	type Target = u32;
+	#[not_safe(irq)]
+	fn deref(&self) -> &u32 { &self.0 }
+}
+impl Drop for Guard {
+	#[not_safe(irq)]
+	fn drop(&mut self) {}
+}
+
+struct Table;
+impl Index<usize> for Table {
+	type Output = u32;
+	#[not_safe(irq)]
+	fn index(&self, _i: usize) -> &u32 { &0 }
+}
+
+fn use_guard(g: &Guard) -> u32 {
+	g.pow(2)
+}
+
+#[req_safe(irq)]
+fn irq_handler(a: Counter, b: Counter, g: &Guard, t: &Table) {
+	let _c = a + b;
+	//~^ ERROR Calling irq-unsafe method from
+	let _v = **g;
+	//~^ ERROR Calling irq-unsafe method from
+	let _i = t[0];
+	//~^ ERROR Calling irq-unsafe method from
+	use_guard(g);
+	//~^ ERROR Calling irq-unsafe method from
+}
+
+#[req_safe(irq)]
+fn irq_handler_drop() {
+	let _g = Guard(0);
+}
+//~^ ERROR Calling irq-unsafe method from
+
+// Moved-out values aren't dropped
+#[req_safe(irq)]
+fn irq_handler_return() -> Guard {
+	let g = Guard(0);
+	g
+}
+
+#[req_safe(irq)]
+fn irq_handler_forget() {
+	let g = Guard(0);
+	std::mem::forget(g);
+}
+
+#[req_safe(irq)]
+fn irq_handler_temporary() -> u32 {
+	let v = Guard(1).0;
+	//~^ ERROR Calling irq-unsafe method from
+	v
+}
+
+#[req_safe(irq)]
+fn irq_handler_assign(g: &mut Guard) {
+	*g = Guard(1);
+	//~^ ERROR Calling irq-unsafe method from
+}
+
+fn main() {
+}
@@ -0,0 +1,12 @@
+// Tag lists not found next to the source file are searched for in the package root (`CARGO_MANIFEST_DIR`)
+// rustc-env:CARGO_MANIFEST_DIR=tests/compile-fail/manifest_dir
+#[allow(unused_extern_crates)]
+#[tagged_safe(print="libstd_manifest.txt")]
+extern crate std as _std;
+
+#[req_safe(print)]
+#[deny(not_tagged_safe)]
+fn main() {
+	println!("Hello World");
+	//~^ ERROR Calling print-unsafe method from
+}
@@ -0,0 +1,12 @@
+// Tag lists not found next to the source file or in the package root are searched for in `TAG_SAFE_PATH`
+// rustc-env:TAG_SAFE_PATH=tests/compile-fail/search_path
+#[allow(unused_extern_crates)]
+#[tagged_safe(print="libstd_search.txt")]
+extern crate std as _std;
+
+#[req_safe(print)]
+#[deny(not_tagged_safe)]
+fn main() {
+	println!("Hello World");
+	//~^ ERROR Calling print-unsafe method from
+}
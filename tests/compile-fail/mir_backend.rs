@@ -0,0 +1,52 @@
+#![tag_safe_config(backend = "mir")]
+#![deny(not_tagged_safe)]
+#![allow(dead_code)]
+
+trait Lock {
+	fn lock(&self);
+}
+
+struct IrqLock;
+impl Lock for IrqLock {
+	fn lock(&self) {}
+}
+
+struct NonIrqLock;
+impl Lock for NonIrqLock {
+	#[not_safe(irq)]
+	fn lock(&self) {}
+}
+
+fn generic<T: Lock>(l: &T) {
+	l.lock()
+}
+
+struct Guard;
+impl Drop for Guard {
+	#[not_safe(irq)]
+	fn drop(&mut self) {}
+}
+
+// Only dropped via drop glue
+struct Holder {
+	_g: Guard,
+}
+
+#[req_safe(irq)]
+fn irq_handler(a: &IrqLock, b: &NonIrqLock) {
+	// Each instance of `generic` is checked with the concrete type
+	generic(a);
+	generic(b);
+	//~^ ERROR Calling irq-unsafe method from
+}
+
+#[req_safe(irq)]
+fn irq_drop(h: Holder) {
+	let _moved = Some(Guard);
+	std::mem::forget(_moved);
+	drop(h);
+	//~^ ERROR Calling irq-unsafe method from
+}
+
+fn main() {
+}
@@ -0,0 +1,59 @@
+// compile-flags: --edition=2018
+#![tag_safe_config(backend = "mir")]
+#![deny(not_tagged_safe)]
+#![allow(dead_code)]
+
+use std::fmt;
+
+#[not_safe(irq)]
+fn acquire_non_irq_spinlock(_l: &u32) {
+}
+
+struct Name;
+impl fmt::Display for Name {
+	#[not_safe(irq)]
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result { f.write_str("name") }
+}
+
+// Closures are reported where they're created, not again where they're called
+#[req_safe(irq)]
+fn irq_handler(items: &[u32]) {
+	items.iter().for_each(|x| {
+		acquire_non_irq_spinlock(x)
+		//~^ ERROR Calling irq-unsafe method from
+	});
+}
+
+#[req_safe(irq)]
+fn irq_handler_call() {
+	let f = || acquire_non_irq_spinlock(&0);
+	//~^ ERROR Calling irq-unsafe method from
+	f();
+}
+
+fn wrapper(items: &[u32]) {
+	items.iter().for_each(|x| acquire_non_irq_spinlock(x));
+}
+
+#[req_safe(irq)]
+fn irq_handler_indirect(items: &[u32]) {
+	wrapper(items);
+	//~^ ERROR Calling irq-unsafe method from
+}
+
+#[req_safe(irq)]
+fn irq_handler_async() {
+	let _f = async {
+		acquire_non_irq_spinlock(&0);
+		//~^ ERROR Calling irq-unsafe method from
+	};
+}
+
+#[req_safe(irq)]
+fn irq_format(n: &Name) -> String {
+	format!("{}", n)
+	//~^ ERROR Calling irq-unsafe method from
+}
+
+fn main() {
+}
@@ -0,0 +1,47 @@
+#![deny(not_tagged_safe)]
+#![allow(dead_code, unconditional_recursion)]
+
+#[not_safe(irq)]
+fn acquire_non_irq_spinlock() {
+}
+
+// `even` and `odd` are mutually recursive, and only `odd` calls the unsafe function
+fn even(n: u32) -> bool {
+	if n == 0 { true } else { odd(n - 1) }
+}
+fn odd(n: u32) -> bool {
+	if n == 0 {
+		acquire_non_irq_spinlock();
+		false
+	}
+	else {
+		even(n - 1)
+	}
+}
+
+#[req_safe(irq)]
+fn irq_handler_odd() {
+	// Visits `odd` first, which recurses through `even`
+	odd(1);
+	//~^ ERROR Calling irq-unsafe method from
+}
+
+#[req_safe(irq)]
+fn irq_handler_even() {
+	even(1);
+	//~^ ERROR Calling irq-unsafe method from
+}
+
+fn self_recursive(n: u32) {
+	if n > 0 {
+		self_recursive(n - 1)
+	}
+}
+
+#[req_safe(irq)]
+fn irq_handler_self() {
+	self_recursive(3);
+}
+
+fn main() {
+}
@@ -0,0 +1,43 @@
+// Calls that resolve to compiler-generated shims are checked through the functions they call
+#![deny(not_tagged_safe)]
+#![allow(dead_code)]
+
+struct Tracked(u32);
+impl Clone for Tracked {
+	#[not_safe(irq)]
+	fn clone(&self) -> Tracked { Tracked(self.0) }
+}
+
+#[derive(Clone)]
+struct Plain(u32);
+
+struct Guard;
+impl Drop for Guard {
+	#[not_safe(irq)]
+	fn drop(&mut self) {}
+}
+
+#[req_safe(irq)]
+fn intrinsics(v: u32) -> f32 {
+	unsafe { ::std::mem::transmute(v) }
+}
+
+#[req_safe(irq)]
+fn clone_tuple(v: &(u32, Tracked)) -> (u32, Tracked) {
+	v.clone()
+	//~^ ERROR Calling irq-unsafe method from
+}
+
+#[req_safe(irq)]
+fn clone_safe_tuple(v: &(u32, Plain)) -> (u32, Plain) {
+	v.clone()
+}
+
+#[req_safe(irq)]
+fn drop_in_place(g: *mut Guard) {
+	unsafe { ::std::ptr::drop_in_place(g) }
+	//~^ ERROR Calling irq-unsafe method from
+}
+
+fn main() {
+}
@@ -0,0 +1,14 @@
+// List entries that don't match any function of the crate are reported
+#![tagged_safe(crate = "std", print(not_safe("std::io::_print", "std::io::no_such_function", "std::no_such_module::*")))]
+//~^ WARNING `std::io::no_such_function` at
+//~| WARNING `std::no_such_module::*` at
+#![tagged_safe(crate = "no_such_crate", print(not_safe("no_such_crate::print")))]
+//~^ WARNING Tag lists are loaded for `no_such_crate`, which isn't a dependency of this crate
+#![allow(dead_code)]
+
+#[req_safe(print)]
+#[deny(not_tagged_safe)]
+fn main() {
+	println!("Hello World");
+    //~^ ERROR Calling print-unsafe method from
+}
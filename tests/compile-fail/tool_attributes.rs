@@ -0,0 +1,23 @@
+#![feature(register_tool)]
+#![register_tool(tag_safe)]
+#![deny(not_tagged_safe)]
+
+#[tag_safe::not(irq)]
+fn unsafe_method() {
+}
+
+#[tag_safe::is(irq)]
+fn safe_wrapper() {
+	unsafe_method()
+}
+
+#[tag_safe::req(irq)]
+fn handler() {
+	safe_wrapper();
+	unsafe_method();
+	//~^ ERROR Calling irq-unsafe method from
+}
+
+fn main() {
+	handler();
+}
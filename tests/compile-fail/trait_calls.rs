@@ -0,0 +1,42 @@
+#![deny(not_tagged_safe)]
+#![allow(dead_code)]
+
+trait Lock {
+	fn lock(&self);
+}
+
+struct IrqLock;
+struct NonIrqLock;
+
+impl Lock for IrqLock {
+	#[is_safe(irq)]
+	fn lock(&self) {}
+}
+impl Lock for NonIrqLock {
+	#[not_safe(irq)]
+	fn lock(&self) {}
+}
+
+#[req_safe(irq)]
+fn irq_handler(a: &IrqLock, b: &NonIrqLock) {
+	a.lock();
+	<IrqLock as Lock>::lock(a);
+	b.lock();
+	//~^ ERROR Calling irq-unsafe method from
+	<NonIrqLock as Lock>::lock(b);
+	//~^ ERROR Calling irq-unsafe method from
+}
+
+fn generic<T: Lock>(l: &T) {
+	// Can't be resolved when checking `generic` itself, and `Lock::lock` isn't tagged
+	l.lock()
+}
+
+#[req_safe(irq)]
+fn irq_handler_generic(a: &IrqLock) {
+	generic(a);
+	//~^ ERROR Calling irq-unsafe method from
+}
+
+fn main() {
+}
@@ -0,0 +1,53 @@
+#![deny(not_tagged_safe)]
+#![allow(dead_code)]
+
+#[not_safe(irq)]
+fn acquire_non_irq_spinlock() {
+}
+
+trait IrqHandler {
+	#[req_safe(irq)]
+	fn handle(&self);
+
+	#[is_safe(irq)]
+	fn ack(&self) {
+		self.handle()
+	}
+}
+
+struct Good;
+impl IrqHandler for Good {
+	fn handle(&self) {
+	}
+}
+
+struct Bad;
+impl IrqHandler for Bad {
+	fn handle(&self) {
+	//~^ ERROR Implementation of `handle` is not irq-safe
+		acquire_non_irq_spinlock()
+	}
+	#[not_safe(irq)]
+	fn ack(&self) {
+	//~^ ERROR Implementation of `ack` is not irq-safe
+	}
+}
+
+trait Device {
+	#[is_safe(irq)]
+	fn poll(&self) {
+		self.read()
+	}
+	fn read(&self);
+}
+
+struct Uart;
+impl Device for Uart {
+//~^ ERROR Inherited default body of `poll` is not irq-safe
+	fn read(&self) {
+		acquire_non_irq_spinlock()
+	}
+}
+
+fn main() {
+}
@@ -1,5 +1,3 @@
-#![feature(custom_attribute,plugin)]
-#![plugin(tag_safe)]
 #![allow(dead_code)]
 
 fn main() {
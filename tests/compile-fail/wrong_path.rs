@@ -1,6 +1,3 @@
-#![feature(plugin)]
-#![plugin(tag_safe)]
-
 #[tagged_safe(foo="some_missing_file")]
 //~^ ERROR Couldn't open tagging list file
 extern crate core;
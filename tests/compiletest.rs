@@ -8,6 +8,8 @@ fn run_mode(mode: &'static str) {
 
     config.mode = cfg_mode;
     config.src_base = PathBuf::from(format!("tests/{}", mode));
+    // The lints are only available through the driver
+    config.rustc_path = PathBuf::from("target/debug/tag-safe-driver");
     config.target_rustcflags = Some("-L target/debug/deps".to_string());
 
     compiletest_rs::run_tests(&config);
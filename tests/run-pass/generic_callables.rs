@@ -0,0 +1,27 @@
+// Generic higher-order functions can be called with safe closures and functions
+#![deny(not_tagged_safe)]
+
+#[is_safe(irq)]
+fn ack_irq() {
+}
+
+fn with_lock<F: FnOnce()>(f: F) {
+	f()
+}
+
+fn map_all<F: FnMut(u32) -> u32>(values: &mut [u32], mut f: F) {
+	for v in values.iter_mut() {
+		*v = f(*v);
+	}
+}
+
+#[req_safe(irq)]
+fn irq_handler(values: &mut [u32]) {
+	with_lock(|| ack_irq());
+	with_lock(ack_irq);
+	map_all(values, |v| v + 1);
+}
+
+fn main() {
+	irq_handler(&mut [1, 2]);
+}